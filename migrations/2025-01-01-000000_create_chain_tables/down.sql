@@ -0,0 +1,4 @@
+DROP TABLE logs;
+DROP TABLE receipts;
+DROP TABLE transactions;
+DROP TABLE blocks;
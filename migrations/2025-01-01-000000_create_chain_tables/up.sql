@@ -0,0 +1,59 @@
+CREATE TABLE blocks (
+    hash TEXT PRIMARY KEY,
+    number BIGINT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    miner TEXT,
+    gas_used BIGINT NOT NULL,
+    gas_limit BIGINT NOT NULL,
+    base_fee_per_gas NUMERIC(78, 0),
+    transaction_count INTEGER NOT NULL
+);
+
+CREATE INDEX blocks_number_idx ON blocks (number);
+
+CREATE TABLE transactions (
+    hash TEXT PRIMARY KEY,
+    block_hash TEXT NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+    block_number BIGINT NOT NULL,
+    transaction_index INTEGER NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT,
+    value NUMERIC(78, 0) NOT NULL,
+    gas NUMERIC(78, 0) NOT NULL,
+    gas_price NUMERIC(78, 0),
+    nonce NUMERIC(78, 0) NOT NULL,
+    input TEXT NOT NULL
+);
+
+CREATE INDEX transactions_block_hash_idx ON transactions (block_hash);
+CREATE INDEX transactions_from_address_idx ON transactions (from_address, block_number);
+CREATE INDEX transactions_to_address_idx ON transactions (to_address, block_number);
+
+CREATE TABLE receipts (
+    transaction_hash TEXT PRIMARY KEY REFERENCES transactions (hash) ON DELETE CASCADE,
+    block_hash TEXT NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+    block_number BIGINT NOT NULL,
+    status SMALLINT,
+    gas_used NUMERIC(78, 0),
+    cumulative_gas_used NUMERIC(78, 0) NOT NULL,
+    effective_gas_price NUMERIC(78, 0),
+    contract_address TEXT
+);
+
+CREATE TABLE logs (
+    block_hash TEXT NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+    log_index INTEGER NOT NULL,
+    block_number BIGINT NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    address TEXT NOT NULL,
+    topic0 TEXT,
+    topic1 TEXT,
+    topic2 TEXT,
+    topic3 TEXT,
+    data TEXT NOT NULL,
+    PRIMARY KEY (block_hash, log_index)
+);
+
+CREATE INDEX logs_address_idx ON logs (address, block_number);
+CREATE INDEX logs_topic0_idx ON logs (topic0, block_number);
@@ -0,0 +1,3 @@
+DROP TABLE token_balances;
+DROP TABLE token_balance_changes;
+DROP TABLE token_transfers;
@@ -0,0 +1,43 @@
+CREATE TABLE token_transfers (
+    block_hash TEXT NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+    log_index INTEGER NOT NULL,
+    batch_index INTEGER NOT NULL,
+    block_number BIGINT NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    token TEXT NOT NULL,
+    standard TEXT NOT NULL,
+    operator TEXT,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    token_id NUMERIC(78, 0),
+    value NUMERIC(78, 0) NOT NULL,
+    PRIMARY KEY (block_hash, log_index, batch_index)
+);
+
+CREATE INDEX token_transfers_from_address_idx ON token_transfers (from_address, block_number);
+CREATE INDEX token_transfers_to_address_idx ON token_transfers (to_address, block_number);
+CREATE INDEX token_transfers_token_idx ON token_transfers (token, block_number);
+
+-- Net change of each balance in each block. ERC-20 balances use token_id 0.
+CREATE TABLE token_balance_changes (
+    block_hash TEXT NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+    block_number BIGINT NOT NULL,
+    holder TEXT NOT NULL,
+    token TEXT NOT NULL,
+    token_id NUMERIC(78, 0) NOT NULL,
+    delta NUMERIC(78, 0) NOT NULL,
+    PRIMARY KEY (block_hash, holder, token, token_id)
+);
+
+CREATE INDEX token_balance_changes_holder_idx
+    ON token_balance_changes (holder, token, token_id, block_number);
+
+-- Running balances as of the latest indexed block.
+CREATE TABLE token_balances (
+    holder TEXT NOT NULL,
+    token TEXT NOT NULL,
+    token_id NUMERIC(78, 0) NOT NULL,
+    balance NUMERIC(78, 0) NOT NULL,
+    block_number BIGINT NOT NULL,
+    PRIMARY KEY (holder, token, token_id)
+);
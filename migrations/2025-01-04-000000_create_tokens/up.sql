@@ -0,0 +1,9 @@
+-- Token metadata as read from the contract; NULL where a call failed.
+CREATE TABLE tokens (
+    address TEXT PRIMARY KEY,
+    name TEXT,
+    symbol TEXT,
+    decimals SMALLINT,
+    total_supply NUMERIC(78, 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
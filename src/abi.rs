@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use ethers::core::{
+    abi::{Abi, Event, EventExt, Function, FunctionExt, ParamType, RawLog, Token},
+    types::{Address, Bytes, Log, Transaction, H256, I256},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error::DwatError;
+use crate::selectors;
+
+/// Optional file in an ABI directory mapping contract addresses to ABI names.
+pub const CONTRACTS_FILE: &str = "contracts.json";
+
+/// Contract ABIs and the addresses they are deployed at.
+///
+/// ABIs are loaded from `<name>.json` files holding either a bare ABI array
+/// or a compiler artifact with an `abi` field. A file named after an address
+/// applies to that address; other names are bound to addresses through
+/// `contracts.json`, an object of `"<address>": "<name>"` entries.
+#[derive(Debug, Clone, Default)]
+pub struct AbiRegistry {
+    abis: HashMap<String, Abi>,
+    contracts: HashMap<Address, String>,
+}
+
+/// A log decoded against its contract's ABI.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedEvent {
+    pub address: Address,
+    pub name: String,
+    pub signature: String,
+    pub topic0: H256,
+    pub params: Vec<DecodedParam>,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<H256>,
+    pub log_index: Option<u64>,
+}
+
+/// One named event or call argument.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedParam {
+    pub name: String,
+    /// Solidity type, e.g. `uint256`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: Value,
+    pub indexed: bool,
+}
+
+/// A transaction's input decoded by its function selector.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedCall {
+    pub transaction_hash: H256,
+    pub to: Option<Address>,
+    #[serde(flatten)]
+    pub input: CallInput,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "input", rename_all = "snake_case")]
+pub enum CallInput {
+    /// No calldata, as in a plain value transfer.
+    Empty,
+    /// A contract creation, whose input is init code.
+    Create,
+    Decoded {
+        selector: Bytes,
+        name: String,
+        signature: String,
+        source: CallSource,
+        args: Vec<DecodedArg>,
+    },
+    /// No known function matches the selector, or the arguments did not
+    /// decode. Inputs shorter than a selector are reported whole.
+    Unknown { selector: Bytes },
+}
+
+/// Where the function a call was decoded against came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallSource {
+    /// An ABI in the registry.
+    Abi,
+    /// The bundled signature list, which has no parameter names.
+    Selectors,
+}
+
+/// One call argument; unnamed when decoded from a bare signature.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedArg {
+    pub name: Option<String>,
+    /// Solidity type, e.g. `uint256`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: Value,
+}
+
+impl AbiRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `*.json` ABI in `dir`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, DwatError> {
+        let dir = dir.as_ref();
+        let mut registry = Self::new();
+        let read_err = |err: std::io::Error| DwatError::Abi(format!("{}: {}", dir.display(), err));
+
+        for entry in fs::read_dir(dir).map_err(read_err)? {
+            let path = entry.map_err(read_err)?.path();
+            let (Some(stem), Some("json")) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|s| s.to_str()),
+            ) else {
+                continue;
+            };
+            if path.file_name().and_then(|s| s.to_str()) == Some(CONTRACTS_FILE) {
+                continue;
+            }
+
+            let abi = read_abi(&path)?;
+            registry.insert(stem, abi);
+            if let Ok(address) = stem.parse::<Address>() {
+                registry.contracts.insert(address, stem.to_string());
+            }
+        }
+
+        let contracts = dir.join(CONTRACTS_FILE);
+        if contracts.exists() {
+            let bytes = fs::read(&contracts).map_err(read_err)?;
+            let map: HashMap<Address, String> = serde_json::from_slice(&bytes)
+                .map_err(|err| DwatError::Abi(format!("{}: {}", contracts.display(), err)))?;
+            for (address, name) in map {
+                registry.register(address, &name)?;
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, abi: Abi) {
+        self.abis.insert(name.into(), abi);
+    }
+
+    /// Binds `address` to the ABI called `name`.
+    pub fn register(&mut self, address: Address, name: &str) -> Result<(), DwatError> {
+        if !self.abis.contains_key(name) {
+            return Err(DwatError::Abi(format!("no ABI named {}", name)));
+        }
+        self.contracts.insert(address, name.to_string());
+        Ok(())
+    }
+
+    pub fn abi(&self, name: &str) -> Option<&Abi> {
+        self.abis.get(name)
+    }
+
+    pub fn abi_for(&self, address: &Address) -> Option<&Abi> {
+        self.contracts
+            .get(address)
+            .and_then(|name| self.abis.get(name))
+    }
+
+    /// All loaded ABIs.
+    pub fn abis(&self) -> impl Iterator<Item = &Abi> {
+        self.abis.values()
+    }
+
+    /// Decodes `log` if its contract is registered and the ABI declares an
+    /// event with its first topic.
+    pub fn decode_log(&self, log: &Log) -> Option<DecodedEvent> {
+        let abi = self.abi_for(&log.address)?;
+        let topic0 = log.topics.first()?;
+        let event = abi
+            .events()
+            .find(|e| !e.anonymous && e.signature() == *topic0)?;
+        decode_event(event, log)
+    }
+
+    /// Decodes every log it has an ABI for, in order.
+    pub fn decode_logs<'a>(&self, logs: impl IntoIterator<Item = &'a Log>) -> Vec<DecodedEvent> {
+        logs.into_iter()
+            .filter_map(|log| self.decode_log(log))
+            .collect()
+    }
+
+    /// Decodes `tx`'s input against, in turn, the ABI registered for its
+    /// recipient, any loaded ABI and the bundled signatures.
+    pub fn decode_call(&self, tx: &Transaction) -> DecodedCall {
+        let input = match (tx.to, tx.input.get(..4)) {
+            (None, _) => CallInput::Create,
+            (Some(_), _) if tx.input.is_empty() => CallInput::Empty,
+            (Some(_), None) => CallInput::Unknown {
+                selector: tx.input.clone(),
+            },
+            (Some(to), Some(selector)) => {
+                let selector: [u8; 4] = selector.try_into().expect("four bytes");
+                self.decode_input(&to, selector, &tx.input)
+                    .unwrap_or_else(|| CallInput::Unknown {
+                        selector: Bytes::from(selector.to_vec()),
+                    })
+            }
+        };
+        DecodedCall {
+            transaction_hash: tx.hash,
+            to: tx.to,
+            input,
+        }
+    }
+
+    /// Decodes the input of every transaction, in order.
+    pub fn decode_calls<'a>(
+        &self,
+        txs: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Vec<DecodedCall> {
+        txs.into_iter().map(|tx| self.decode_call(tx)).collect()
+    }
+
+    fn decode_input(&self, to: &Address, selector: [u8; 4], input: &[u8]) -> Option<CallInput> {
+        let matches = |function: &&Function| function.short_signature() == selector;
+        let registered = self
+            .abi_for(to)
+            .into_iter()
+            .flat_map(|abi| abi.functions())
+            .find(matches);
+        let loaded = || self.abis().flat_map(|abi| abi.functions()).find(matches);
+
+        if let Some(input) = registered
+            .or_else(loaded)
+            .and_then(|function| decode_function(function, input, CallSource::Abi))
+        {
+            return Some(input);
+        }
+
+        // Prefer a signature the input is the exact encoding of, in case of
+        // selector collisions.
+        let candidates = selectors::lookup(selector);
+        candidates
+            .iter()
+            .find(|function| {
+                function.decode_input(&input[4..]).is_ok_and(|tokens| {
+                    function.encode_input(&tokens).ok().as_deref() == Some(input)
+                })
+            })
+            .or_else(|| candidates.first())
+            .and_then(|function| decode_function(function, input, CallSource::Selectors))
+    }
+}
+
+fn decode_function(function: &Function, input: &[u8], source: CallSource) -> Option<CallInput> {
+    let tokens = function.decode_input(&input[4..]).ok()?;
+    let args = function
+        .inputs
+        .iter()
+        .zip(tokens)
+        .map(|(param, token)| DecodedArg {
+            name: Some(param.name.clone()).filter(|name| !name.is_empty()),
+            kind: param.kind.to_string(),
+            value: token_to_json(&token, &param.kind),
+        })
+        .collect();
+
+    Some(CallInput::Decoded {
+        selector: Bytes::from(input[..4].to_vec()),
+        name: function.name.clone(),
+        signature: function.abi_signature(),
+        source,
+        args,
+    })
+}
+
+fn read_abi(path: &Path) -> Result<Abi, DwatError> {
+    let err = |msg: String| DwatError::Abi(format!("{}: {}", path.display(), msg));
+    let bytes = fs::read(path).map_err(|e| err(e.to_string()))?;
+    let value: Value = serde_json::from_slice(&bytes).map_err(|e| err(e.to_string()))?;
+
+    // Compiler artifacts wrap the ABI in an object.
+    let abi = match value {
+        Value::Object(mut artifact) => artifact
+            .remove("abi")
+            .ok_or_else(|| err("no abi field".into()))?,
+        abi => abi,
+    };
+    serde_json::from_value(abi).map_err(|e| err(e.to_string()))
+}
+
+/// Decodes `log` against `event`, whose signature must match its first topic.
+pub fn decode_event(event: &Event, log: &Log) -> Option<DecodedEvent> {
+    let raw = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let parsed = event.parse_log(raw).ok()?;
+
+    let params = event
+        .inputs
+        .iter()
+        .zip(parsed.params)
+        .map(|(input, param)| {
+            // Indexed dynamic values are only available as their hash.
+            let kind = match (&input.kind, input.indexed) {
+                (
+                    ParamType::String
+                    | ParamType::Bytes
+                    | ParamType::Array(_)
+                    | ParamType::Tuple(_),
+                    true,
+                ) => ParamType::FixedBytes(32),
+                (kind, _) => kind.clone(),
+            };
+            DecodedParam {
+                name: param.name,
+                kind: input.kind.to_string(),
+                value: token_to_json(&param.value, &kind),
+                indexed: input.indexed,
+            }
+        })
+        .collect();
+
+    Some(DecodedEvent {
+        address: log.address,
+        name: event.name.clone(),
+        signature: event.abi_signature(),
+        topic0: event.signature(),
+        params,
+        block_number: log.block_number.map(|n| n.as_u64()),
+        transaction_hash: log.transaction_hash,
+        log_index: log.log_index.map(|i| i.low_u64()),
+    })
+}
+
+/// Renders a token as JSON. Integers become decimal strings so that 256-bit
+/// values survive, with signed types read as two's complement `I256`.
+pub fn token_to_json(token: &Token, kind: &ParamType) -> Value {
+    match (token, kind) {
+        (Token::Address(address), _) => json!(address),
+        (Token::Int(value), _) => json!(I256::from_raw(*value).to_string()),
+        (Token::Uint(value), _) => json!(value.to_string()),
+        (Token::Bool(value), _) => json!(value),
+        (Token::String(value), _) => json!(value),
+        (Token::Bytes(bytes) | Token::FixedBytes(bytes), _) => {
+            json!(ethers::core::types::Bytes::from(bytes.clone()))
+        }
+        (
+            Token::Array(items) | Token::FixedArray(items),
+            ParamType::Array(inner) | ParamType::FixedArray(inner, _),
+        ) => Value::Array(
+            items
+                .iter()
+                .map(|item| token_to_json(item, inner))
+                .collect(),
+        ),
+        (Token::Tuple(items), ParamType::Tuple(kinds)) => Value::Array(
+            items
+                .iter()
+                .zip(kinds)
+                .map(|(item, kind)| token_to_json(item, kind))
+                .collect(),
+        ),
+        (Token::Array(items) | Token::FixedArray(items) | Token::Tuple(items), _) => {
+            Value::Array(items.iter().map(|item| token_to_json(item, kind)).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::parse_abi;
+    use ethers::core::types::U256;
+
+    use super::*;
+
+    fn tx(to: Option<Address>, input: Vec<u8>) -> Transaction {
+        Transaction {
+            to,
+            input: input.into(),
+            ..Default::default()
+        }
+    }
+
+    fn transfer_input() -> Vec<u8> {
+        let function = selectors::lookup([0xa9, 0x05, 0x9c, 0xbb])[0].clone();
+        function
+            .encode_input(&[
+                Token::Address(Address::repeat_byte(2)),
+                Token::Uint(U256::from(7)),
+            ])
+            .unwrap()
+    }
+
+    fn registry() -> AbiRegistry {
+        let mut registry = AbiRegistry::new();
+        let abi =
+            parse_abi(&["function transfer(address to, uint256 amount) returns (bool)"]).unwrap();
+        registry.insert("token", abi);
+        registry
+    }
+
+    #[test]
+    fn reports_creations_and_empty_or_short_input() {
+        let registry = AbiRegistry::new();
+        let to = Some(Address::repeat_byte(1));
+
+        assert!(matches!(
+            registry.decode_call(&tx(None, transfer_input())).input,
+            CallInput::Create
+        ));
+        assert!(matches!(
+            registry.decode_call(&tx(to, Vec::new())).input,
+            CallInput::Empty
+        ));
+        match registry.decode_call(&tx(to, vec![1, 2])).input {
+            CallInput::Unknown { selector } => assert_eq!(selector.to_vec(), [1, 2]),
+            input => panic!("unexpected {:?}", input),
+        }
+        match registry
+            .decode_call(&tx(to, vec![0xde, 0xad, 0xbe, 0xef, 0]))
+            .input
+        {
+            CallInput::Unknown { selector } => {
+                assert_eq!(selector.to_vec(), [0xde, 0xad, 0xbe, 0xef])
+            }
+            input => panic!("unexpected {:?}", input),
+        }
+    }
+
+    #[test]
+    fn decodes_against_loaded_abis_with_names() {
+        let call = registry().decode_call(&tx(Some(Address::repeat_byte(1)), transfer_input()));
+        let CallInput::Decoded {
+            name, source, args, ..
+        } = call.input
+        else {
+            panic!("not decoded");
+        };
+        assert_eq!(name, "transfer");
+        assert_eq!(source, CallSource::Abi);
+        let args: Vec<_> = args
+            .iter()
+            .map(|arg| (arg.name.as_deref(), arg.kind.as_str()))
+            .collect();
+        assert_eq!(args, [(Some("to"), "address"), (Some("amount"), "uint256")]);
+    }
+
+    #[test]
+    fn falls_back_to_bundled_signatures() {
+        let call =
+            AbiRegistry::new().decode_call(&tx(Some(Address::repeat_byte(1)), transfer_input()));
+        let CallInput::Decoded {
+            selector,
+            signature,
+            source,
+            args,
+            ..
+        } = call.input
+        else {
+            panic!("not decoded");
+        };
+        assert_eq!(selector.to_vec(), [0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(source, CallSource::Selectors);
+        assert!(signature.starts_with("transfer(address,uint256)"));
+        assert!(args.iter().all(|arg| arg.name.is_none()));
+        assert_eq!(
+            args[1].value,
+            token_to_json(&Token::Uint(7.into()), &ParamType::Uint(256))
+        );
+    }
+
+    #[test]
+    fn reports_undecodable_arguments_as_unknown() {
+        let mut input = transfer_input();
+        input.truncate(4 + 16);
+        let call = registry().decode_call(&tx(Some(Address::repeat_byte(1)), input));
+        assert!(matches!(call.input, CallInput::Unknown { .. }));
+    }
+}
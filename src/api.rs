@@ -0,0 +1,193 @@
+use actix_web::{error, get, http::StatusCode, web, App, HttpResponse, HttpServer, ResponseError};
+use serde::Deserialize;
+
+use crate::db::{self, PgPool};
+use crate::error::DwatError;
+use crate::queries::{self, BlockRef, Cursor, LogFilter};
+
+/// Page size when a request does not ask for one.
+const DEFAULT_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+fn parse_cursor(cursor: Option<&str>) -> Result<Option<Cursor>, ApiError> {
+    cursor
+        .map(str::parse)
+        .transpose()
+        .map_err(ApiError::BadRequest)
+}
+
+#[derive(Debug, Deserialize)]
+struct LogQuery {
+    address: Option<String>,
+    topic0: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceQuery {
+    token: Option<String>,
+    block: Option<i64>,
+}
+
+/// An error rendered as a JSON body.
+#[derive(Debug)]
+enum ApiError {
+    BadRequest(DwatError),
+    NotFound,
+    Internal(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(err) => write!(f, "{}", err),
+            ApiError::NotFound => write!(f, "not found"),
+            ApiError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .json(serde_json::json!({ "error": self.to_string() }))
+    }
+}
+
+impl From<DwatError> for ApiError {
+    fn from(err: DwatError) -> Self {
+        ApiError::Internal(err.to_string())
+    }
+}
+
+impl From<error::BlockingError> for ApiError {
+    fn from(err: error::BlockingError) -> Self {
+        ApiError::Internal(err.to_string())
+    }
+}
+
+/// Runs `query` on a pooled connection off the async workers.
+async fn with_conn<T, F>(pool: &web::Data<PgPool>, query: F) -> Result<T, ApiError>
+where
+    F: FnOnce(&mut diesel::PgConnection) -> Result<T, DwatError> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    web::block(move || {
+        let mut conn = db::pooled(&pool)?;
+        query(&mut conn)
+    })
+    .await?
+    .map_err(ApiError::from)
+}
+
+#[get("/blocks/{id}")]
+async fn block(pool: web::Data<PgPool>, id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let id: BlockRef = id.parse().map_err(ApiError::BadRequest)?;
+    let block = with_conn(&pool, move |conn| queries::block(conn, &id)).await?;
+    block
+        .map(|block| HttpResponse::Ok().json(block))
+        .ok_or(ApiError::NotFound)
+}
+
+#[get("/tx/{hash}")]
+async fn transaction(
+    pool: web::Data<PgPool>,
+    hash: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let hash = hash.into_inner();
+    let tx = with_conn(&pool, move |conn| queries::transaction(conn, &hash)).await?;
+    tx.map(|tx| HttpResponse::Ok().json(tx))
+        .ok_or(ApiError::NotFound)
+}
+
+#[get("/address/{address}/transactions")]
+async fn address_transactions(
+    pool: web::Data<PgPool>,
+    address: web::Path<String>,
+    page: web::Query<PageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner();
+    let cursor = parse_cursor(page.cursor.as_deref())?;
+    let limit = page.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let page = with_conn(&pool, move |conn| {
+        queries::address_transactions(conn, &address, cursor, limit)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Balances are net flows since the first indexed block and go negative for
+/// holders that received tokens before it.
+#[get("/address/{address}/balances")]
+async fn address_balances(
+    pool: web::Data<PgPool>,
+    address: web::Path<String>,
+    query: web::Query<BalanceQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner();
+    let query = query.into_inner();
+
+    let balances = with_conn(&pool, move |conn| {
+        queries::balances(conn, &address, query.token.as_deref(), query.block)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(balances))
+}
+
+#[get("/logs")]
+async fn logs(
+    pool: web::Data<PgPool>,
+    query: web::Query<LogQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let filter = LogFilter {
+        address: query.address,
+        topic0: query.topic0,
+        from_block: query.from,
+        to_block: query.to,
+    };
+
+    let page = with_conn(&pool, move |conn| {
+        queries::logs(conn, &filter, cursor, limit)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Registers the query routes on an actix-web app.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(block)
+        .service(transaction)
+        .service(address_transactions)
+        .service(address_balances)
+        .service(logs);
+}
+
+/// Serves the query API on `bind` until the server is stopped.
+pub async fn serve(bind: &str, pool: PgPool) -> std::io::Result<()> {
+    let pool = web::Data::new(pool);
+    HttpServer::new(move || App::new().app_data(pool.clone()).configure(configure))
+        .bind(bind)?
+        .run()
+        .await
+}
@@ -0,0 +1,187 @@
+use std::ops::RangeInclusive;
+
+use ethers::providers::{JsonRpcClient, Provider, StreamExt};
+use futures::stream;
+use tokio::{task, time};
+
+use crate::block::{BlockData, BlockFetcher};
+use crate::error::DwatError;
+use crate::reconnect::ReconnectPolicy;
+use crate::sink::Sink;
+use crate::source::ChainBlock;
+
+/// Settings for indexing a fixed range of historical blocks.
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    /// First block to index.
+    pub from: u64,
+    /// Last block to index, inclusive.
+    pub to: u64,
+    /// Chunks fetched concurrently, one block at a time each.
+    pub workers: usize,
+    /// Blocks per chunk.
+    pub chunk_size: u64,
+    /// Delays between attempts at a failed chunk; `max_attempts` bounds the
+    /// number of retries.
+    pub retry: ReconnectPolicy,
+}
+
+impl BackfillConfig {
+    pub fn new(from: u64, to: u64) -> Self {
+        Self {
+            from,
+            to,
+            workers: 4,
+            chunk_size: 100,
+            retry: ReconnectPolicy {
+                max_attempts: Some(5),
+                ..ReconnectPolicy::default()
+            },
+        }
+    }
+
+    fn chunks(&self) -> impl Iterator<Item = RangeInclusive<u64>> {
+        let (to, size) = (self.to, self.chunk_size.max(1));
+        (self.from..=self.to)
+            .step_by(size as usize)
+            .map(move |start| start..=(start + size - 1).min(to))
+    }
+}
+
+/// Fetches the blocks in `config`'s range with their receipts and writes
+/// them to `sink` in ascending order.
+///
+/// Up to `config.workers` chunks are in flight at once, each fetching one
+/// block at a time, so `workers` bounds the blocks requested concurrently.
+/// Blocks that fail are retried on their own. Must run on a multi-threaded
+/// runtime.
+pub async fn backfill<P: JsonRpcClient, S: Sink>(
+    provider: &Provider<P>,
+    config: &BackfillConfig,
+    sink: &mut S,
+) -> Result<(), DwatError> {
+    if config.from > config.to {
+        return Err(DwatError::Config(format!(
+            "empty backfill range {}..={}",
+            config.from, config.to
+        )));
+    }
+
+    let fetcher = BlockFetcher::new();
+    let mut chunks = stream::iter(config.chunks())
+        .map(|range| fetch_chunk(provider, &fetcher, range, &config.retry))
+        .buffered(config.workers.max(1));
+
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        task::block_in_place(|| {
+            chunk
+                .into_iter()
+                .map(ChainBlock::from)
+                .try_for_each(|block| sink.apply(&block))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the blocks of `range` one at a time, retrying the ones that
+/// failed until all are in or `retry` gives up.
+async fn fetch_chunk<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    fetcher: &BlockFetcher,
+    range: RangeInclusive<u64>,
+    retry: &ReconnectPolicy,
+) -> Result<Vec<BlockData>, DwatError> {
+    let start = *range.start();
+    let mut blocks: Vec<Option<BlockData>> = range.clone().map(|_| None).collect();
+    let mut backoff = retry.backoff();
+    loop {
+        let missing: Vec<u64> = range
+            .clone()
+            .filter(|number| blocks[(number - start) as usize].is_none())
+            .collect();
+        let mut failure = None;
+        let mut fetched = stream::iter(missing)
+            .map(|number| async move { (number, fetcher.fetch(provider, number).await) })
+            .buffered(1);
+        while let Some((number, result)) = fetched.next().await {
+            match result {
+                Ok(block) => blocks[(number - start) as usize] = Some(block),
+                Err(err) => failure = Some(err),
+            }
+        }
+
+        let Some(err) = failure else {
+            return Ok(blocks.into_iter().flatten().collect());
+        };
+        match backoff.next_delay() {
+            Some(delay) => time::sleep(delay).await,
+            None => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(from: u64, to: u64, chunk_size: u64) -> Vec<RangeInclusive<u64>> {
+        BackfillConfig {
+            chunk_size,
+            ..BackfillConfig::new(from, to)
+        }
+        .chunks()
+        .collect()
+    }
+
+    #[test]
+    fn splits_the_range_into_chunks() {
+        assert_eq!(chunks(10, 29, 10), [10..=19, 20..=29]);
+        assert_eq!(chunks(10, 25, 10), [10..=19, 20..=25]);
+        assert_eq!(chunks(10, 10, 10), [10..=10]);
+        assert_eq!(chunks(0, 2, 1), [0..=0, 1..=1, 2..=2]);
+    }
+
+    #[test]
+    fn treats_a_zero_chunk_size_as_one() {
+        assert_eq!(chunks(5, 6, 0), [5..=5, 6..=6]);
+    }
+
+    #[tokio::test]
+    async fn refetches_only_the_blocks_that_failed() {
+        use ethers::core::types::{Block, Transaction, TransactionReceipt, H256, U64};
+        use ethers::providers::{JsonRpcError, MockResponse};
+        use std::time::Duration;
+
+        let block = |number: u64| Block::<Transaction> {
+            hash: Some(H256::from_low_u64_be(number)),
+            number: Some(U64::from(number)),
+            ..Default::default()
+        };
+        let (provider, mock) = Provider::mocked();
+        // Served last to first: block 1 and its receipts, a failure for
+        // block 2, then block 2 again on the retry.
+        mock.push::<Vec<TransactionReceipt>, _>(Vec::new()).unwrap();
+        mock.push(block(2)).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".into(),
+            data: None,
+        }));
+        mock.push::<Vec<TransactionReceipt>, _>(Vec::new()).unwrap();
+        mock.push(block(1)).unwrap();
+
+        let retry = ReconnectPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_attempts: Some(1),
+            ..ReconnectPolicy::default()
+        };
+        let blocks = fetch_chunk(&provider, &BlockFetcher::new(), 1..=2, &retry)
+            .await
+            .unwrap();
+
+        let numbers: Vec<_> = blocks.iter().map(|b| b.block.number).collect();
+        assert_eq!(numbers, [Some(U64::from(1)), Some(U64::from(2))]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ethers::{
+    core::types::{Block, BlockId, BlockNumber, Log, Transaction, TransactionReceipt, H256},
+    providers::{JsonRpcClient, Middleware, Provider, ProviderError, RpcError},
+};
+use futures::future::try_join_all;
+use serde::Serialize;
+
+use crate::error::DwatError;
+
+/// JSON-RPC codes for a method or parameter form the node does not support.
+const UNSUPPORTED: [i64; 2] = [-32601, -32602];
+
+/// A block with its transaction objects and their receipts.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockData {
+    pub block: Block<Transaction>,
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+impl BlockData {
+    /// Fetches the block identified by `id` and the receipt of every
+    /// transaction in it.
+    pub async fn fetch<P: JsonRpcClient>(
+        provider: &Provider<P>,
+        id: impl Into<BlockId>,
+    ) -> Result<Self, DwatError> {
+        BlockFetcher::new().fetch(provider, id).await
+    }
+
+    /// The block with transaction hashes in place of transaction objects.
+    pub fn header(&self) -> Block<H256> {
+        self.block.clone().into()
+    }
+
+    /// Every log in the block, in order.
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.receipts.iter().flat_map(|receipt| &receipt.logs)
+    }
+}
+
+/// Fetches full blocks, using `eth_getBlockReceipts` while the node
+/// supports it and one `eth_getTransactionReceipt` per transaction after.
+#[derive(Debug)]
+pub struct BlockFetcher {
+    block_receipts: AtomicBool,
+}
+
+impl Default for BlockFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockFetcher {
+    pub fn new() -> Self {
+        Self {
+            block_receipts: AtomicBool::new(true),
+        }
+    }
+
+    /// Fetches the block identified by `id` and the receipt of every
+    /// transaction in it.
+    pub async fn fetch<P: JsonRpcClient>(
+        &self,
+        provider: &Provider<P>,
+        id: impl Into<BlockId>,
+    ) -> Result<BlockData, DwatError> {
+        let id = id.into();
+        let block = provider
+            .get_block_with_txs(id)
+            .await?
+            .ok_or_else(|| missing(id))?;
+        let hash = block.hash.ok_or_else(|| missing(id))?;
+
+        if let Some(receipts) = self.block_receipts(provider, hash).await? {
+            // A reorg between the two calls leaves receipts of another block.
+            let consistent = receipts.len() == block.transactions.len()
+                && receipts.iter().all(|r| r.block_hash == Some(hash));
+            if consistent {
+                return Ok(BlockData { block, receipts });
+            }
+        }
+
+        let receipts = try_join_all(block.transactions.iter().map(|tx| async move {
+            provider
+                .get_transaction_receipt(tx.hash)
+                .await?
+                .ok_or(DwatError::UnknownTransaction(tx.hash))
+        }))
+        .await?;
+
+        Ok(BlockData { block, receipts })
+    }
+
+    /// All receipts of block `hash` in one call, or `None` if the node
+    /// cannot serve them that way.
+    async fn block_receipts<P: JsonRpcClient>(
+        &self,
+        provider: &Provider<P>,
+        hash: H256,
+    ) -> Result<Option<Vec<TransactionReceipt>>, DwatError> {
+        if !self.block_receipts.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        match provider.request("eth_getBlockReceipts", [hash]).await {
+            Ok(receipts) => Ok(receipts),
+            Err(err) if unsupported(&err) => {
+                self.block_receipts.store(false, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+fn unsupported(err: &ProviderError) -> bool {
+    err.as_error_response()
+        .is_some_and(|response| UNSUPPORTED.contains(&response.code))
+}
+
+fn missing(id: BlockId) -> DwatError {
+    match id {
+        BlockId::Hash(hash) => DwatError::UnknownBlock(hash),
+        BlockId::Number(BlockNumber::Number(number)) => DwatError::MissingBlock(number.as_u64()),
+        BlockId::Number(tag) => DwatError::Config(format!("node has no {} block", tag)),
+    }
+}
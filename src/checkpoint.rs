@@ -0,0 +1,129 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use diesel::dsl::now;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use ethers::core::types::{Block, H256};
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::DwatError;
+use crate::models::hex;
+use crate::schema::checkpoints;
+
+/// The last block that was fully processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub number: u64,
+    pub hash: H256,
+}
+
+impl Checkpoint {
+    /// Returns `None` for pending blocks, which have neither number nor hash.
+    pub fn of<T>(block: &Block<T>) -> Option<Self> {
+        Some(Self {
+            number: block.number?.as_u64(),
+            hash: block.hash?,
+        })
+    }
+
+    /// A header carrying only the number and hash, enough to anchor a
+    /// [`ReorgTracker`](crate::ReorgTracker).
+    pub fn header(&self) -> Block<H256> {
+        Block {
+            number: Some(self.number.into()),
+            hash: Some(self.hash),
+            ..Default::default()
+        }
+    }
+}
+
+/// Somewhere to remember progress across restarts.
+pub trait CheckpointStore {
+    fn load(&mut self) -> Result<Option<Checkpoint>, DwatError>;
+
+    fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), DwatError>;
+}
+
+/// Keeps the checkpoint as JSON in a local file.
+pub struct FileCheckpoint {
+    path: PathBuf,
+}
+
+impl FileCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CheckpointStore for FileCheckpoint {
+    fn load(&mut self) -> Result<Option<Checkpoint>, DwatError> {
+        match fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|err| DwatError::Checkpoint(err.to_string())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(DwatError::Checkpoint(err.to_string())),
+        }
+    }
+
+    fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), DwatError> {
+        let json =
+            serde_json::to_vec(checkpoint).map_err(|err| DwatError::Checkpoint(err.to_string()))?;
+
+        // Write then rename so a crash never leaves a truncated file behind.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|err| DwatError::Checkpoint(err.to_string()))
+    }
+}
+
+/// Keeps named checkpoints in the `checkpoints` table.
+pub struct PgCheckpoint {
+    conn: PgConnection,
+    name: String,
+}
+
+impl PgCheckpoint {
+    pub fn connect(url: &str, name: impl Into<String>) -> Result<Self, DwatError> {
+        Ok(Self {
+            conn: db::connect(url)?,
+            name: name.into(),
+        })
+    }
+}
+
+impl CheckpointStore for PgCheckpoint {
+    fn load(&mut self) -> Result<Option<Checkpoint>, DwatError> {
+        let row: Option<(i64, String)> = checkpoints::table
+            .find(&self.name)
+            .select((checkpoints::block_number, checkpoints::block_hash))
+            .first(&mut self.conn)
+            .optional()?;
+
+        row.map(|(number, hash)| {
+            Ok(Checkpoint {
+                number: number as u64,
+                hash: hash
+                    .parse()
+                    .map_err(|_| DwatError::Checkpoint(format!("invalid block hash: {}", hash)))?,
+            })
+        })
+        .transpose()
+    }
+
+    fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), DwatError> {
+        let number = checkpoints::block_number.eq(checkpoint.number as i64);
+        let hash = checkpoints::block_hash.eq(hex(checkpoint.hash));
+        diesel::insert_into(checkpoints::table)
+            .values((checkpoints::name.eq(&self.name), number, hash.clone()))
+            .on_conflict(checkpoints::name)
+            .do_update()
+            .set((number, hash, checkpoints::updated_at.eq(now)))
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use std::env;
+use std::time::Duration;
+
+use crate::checkpoint::Checkpoint;
+use crate::delivery::DeliveryMode;
+use crate::error::DwatError;
+use crate::reconnect::ReconnectPolicy;
+
+/// Headers kept for reorg detection unless `REORG_DEPTH` says otherwise.
+pub const DEFAULT_REORG_DEPTH: usize = 64;
+
+/// Blocks fetched concurrently while catching up, unless `BACKFILL_BATCH`
+/// says otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 50;
+
+/// How often HTTP endpoints are polled for new blocks unless
+/// `POLL_INTERVAL_MS` says otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings for a head subscription.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Endpoint of the node: a WebSocket or HTTP URL, or an IPC socket path.
+    pub url: String,
+    /// Further endpoints to fail over to when `url` is unhealthy.
+    pub fallbacks: Vec<String>,
+    /// Number of endpoints that must report the same hash for a block
+    /// before it is delivered; `None` trusts the active endpoint alone.
+    pub quorum: Option<usize>,
+    /// Backoff between attempts to re-establish a failed connection, and
+    /// how long a silent one is trusted.
+    pub reconnect: ReconnectPolicy,
+    /// Number of recent headers a [`ChainStream`](crate::ChainStream) keeps
+    /// to resolve reorganisations.
+    pub reorg_depth: usize,
+    /// When a [`ChainStream`](crate::ChainStream) releases applied blocks.
+    pub delivery: DeliveryMode,
+    /// Last block already processed; the stream starts right after it.
+    pub resume_from: Option<Checkpoint>,
+    /// Blocks fetched concurrently when filling gaps and catching up.
+    pub batch_size: usize,
+    /// Delay between `eth_blockNumber` calls on endpoints without
+    /// subscriptions.
+    pub poll_interval: Duration,
+}
+
+impl StreamConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            fallbacks: Vec::new(),
+            quorum: None,
+            reconnect: ReconnectPolicy::default(),
+            reorg_depth: DEFAULT_REORG_DEPTH,
+            delivery: DeliveryMode::default(),
+            resume_from: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Reads `WS_ENDPOINT` (a comma-separated list whose first entry is
+    /// preferred) and the optional `QUORUM`, `REORG_DEPTH`, `DELIVERY_MODE`,
+    /// `BACKFILL_BATCH` and `POLL_INTERVAL_MS`.
+    pub fn from_env() -> Result<Self, DwatError> {
+        let urls = env::var("WS_ENDPOINT")
+            .map_err(|_| DwatError::Config("WS_ENDPOINT must be set in environment".into()))?;
+        let mut urls = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(String::from);
+        let url = urls
+            .next()
+            .ok_or_else(|| DwatError::Config("WS_ENDPOINT is empty".into()))?;
+
+        let mut config = Self::new(url);
+        config.fallbacks = urls.collect();
+        if let Some(quorum) = parse_var::<usize>("QUORUM")? {
+            if quorum > config.endpoints().count() {
+                return Err(DwatError::Config(format!(
+                    "QUORUM of {} exceeds the number of endpoints",
+                    quorum
+                )));
+            }
+            config.quorum = Some(quorum);
+        }
+        if let Some(depth) = parse_var("REORG_DEPTH")? {
+            config.reorg_depth = depth;
+        }
+        if let Some(mode) = parse_var("DELIVERY_MODE")? {
+            config.delivery = mode;
+        }
+        if let Some(batch_size) = parse_var("BACKFILL_BATCH")? {
+            config.batch_size = batch_size;
+        }
+        if let Some(ms) = parse_var("POLL_INTERVAL_MS")? {
+            config.poll_interval = Duration::from_millis(ms);
+        }
+        Ok(config)
+    }
+
+    /// `url` followed by the fallbacks.
+    pub fn endpoints(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url.as_str()).chain(self.fallbacks.iter().map(String::as_str))
+    }
+}
+
+/// Parses an optional environment variable.
+pub(crate) fn parse_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, DwatError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| DwatError::Config(format!("{} has an invalid value: {}", name, value))),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses an optional comma-separated environment variable.
+pub(crate) fn parse_list_var<T: std::str::FromStr>(
+    name: &str,
+) -> Result<Option<Vec<T>>, DwatError> {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+    value
+        .split(',')
+        .map(|item| {
+            item.trim()
+                .parse()
+                .map_err(|_| DwatError::Config(format!("{} has an invalid value: {}", name, item)))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
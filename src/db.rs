@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::env;
+
+use bigdecimal::{BigDecimal, Zero};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::sql_types::BigInt;
+use diesel::upsert::excluded;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use ethers::core::types::Address;
+
+use crate::block::BlockData;
+use crate::error::DwatError;
+use crate::models::{
+    hex, numeric, BalanceChangeRow, BlockRow, LogRow, ReceiptRow, TokenBalanceRow,
+    TokenTransferRow, TransactionRow,
+};
+use crate::schema::{
+    blocks, logs, receipts, token_balance_changes, token_balances, token_transfers, transactions,
+};
+use crate::sink::Sink;
+use crate::source::{Chain, ChainBlock, Head, NativeBlock};
+use crate::transfers::{block_transfers, TokenTransfer};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Rows per `INSERT`, keeping statements well under Postgres' bind limit.
+const CHUNK: usize = 1000;
+
+diesel::define_sql_function!(fn greatest(a: BigInt, b: BigInt) -> BigInt);
+
+/// Opens a connection to `url` and brings the schema up to date.
+pub fn connect(url: &str) -> Result<PgConnection, DwatError> {
+    let mut conn = PgConnection::establish(url)?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .map_err(|err| DwatError::Migration(err.to_string()))?;
+    Ok(conn)
+}
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+
+/// Builds a connection pool for `url`, applying migrations first.
+pub fn pool(url: &str) -> Result<PgPool, DwatError> {
+    connect(url)?;
+    Pool::builder()
+        .build(ConnectionManager::new(url))
+        .map_err(|err| DwatError::Config(format!("database pool: {}", err)))
+}
+
+/// Checks a connection out of `pool`.
+pub fn pooled(
+    pool: &PgPool,
+) -> Result<PooledConnection<ConnectionManager<PgConnection>>, DwatError> {
+    pool.get()
+        .map_err(|err| DwatError::Config(format!("database pool: {}", err)))
+}
+
+/// Reads the database URL from `DATABASE_URL`.
+pub fn database_url() -> Result<String, DwatError> {
+    env::var("DATABASE_URL")
+        .map_err(|_| DwatError::Config("DATABASE_URL must be set in environment".into()))
+}
+
+/// Writes blocks, transactions, receipts, logs and token transfers to
+/// Postgres, keeping running token balances.
+///
+/// Each block is written in a single transaction and every row is upserted,
+/// so replaying a block after a restart never duplicates data. Balances only
+/// move the first time a block is stored, and reverting a block recomputes
+/// the balances it touched from the remaining blocks.
+pub struct PgSink {
+    conn: PgConnection,
+}
+
+impl PgSink {
+    pub fn connect(url: &str) -> Result<Self, DwatError> {
+        Ok(Self {
+            conn: connect(url)?,
+        })
+    }
+
+    pub fn from_env() -> Result<Self, DwatError> {
+        Self::connect(&database_url()?)
+    }
+
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut self.conn
+    }
+}
+
+impl Sink for PgSink {
+    fn apply(&mut self, block: &ChainBlock) -> Result<(), DwatError> {
+        match &block.native {
+            NativeBlock::Evm(data) => self.apply_evm(data),
+            NativeBlock::Solana(_) => Err(unsupported(block.head.chain)),
+        }
+    }
+
+    fn revert(&mut self, head: &Head) -> Result<(), DwatError> {
+        if head.chain != Chain::Evm {
+            return Err(unsupported(head.chain));
+        }
+        let hash = &head.hash;
+        self.conn.transaction(|conn| {
+            let touched: Vec<(String, String, BigDecimal)> = token_balance_changes::table
+                .filter(token_balance_changes::block_hash.eq(hash))
+                .select((
+                    token_balance_changes::holder,
+                    token_balance_changes::token,
+                    token_balance_changes::token_id,
+                ))
+                .load(conn)?;
+
+            // Transactions, receipts, logs, transfers and balance changes
+            // cascade from the block row.
+            diesel::delete(blocks::table.filter(blocks::hash.eq(hash))).execute(conn)?;
+
+            for (holder, token, token_id) in touched {
+                recompute_balance(conn, &holder, &token, &token_id)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn stored(&mut self, number: u64) -> Result<Option<Head>, DwatError> {
+        let row: Option<(String, String, i64)> = blocks::table
+            .filter(blocks::number.eq(number as i64))
+            .select((blocks::hash, blocks::parent_hash, blocks::timestamp))
+            .first(&mut self.conn)
+            .optional()?;
+
+        Ok(row.map(|(hash, parent_hash, timestamp)| Head {
+            chain: Chain::Evm,
+            number,
+            hash,
+            parent_number: number.checked_sub(1),
+            parent_hash,
+            timestamp: Some(timestamp),
+        }))
+    }
+}
+
+impl PgSink {
+    fn apply_evm(&mut self, data: &BlockData) -> Result<(), DwatError> {
+        let block = BlockRow::from(&data.block);
+        let txs: Vec<TransactionRow> = data.block.transactions.iter().map(Into::into).collect();
+        let receipts: Vec<ReceiptRow> = data.receipts.iter().map(Into::into).collect();
+        let logs: Vec<LogRow> = data
+            .receipts
+            .iter()
+            .flat_map(|r| r.logs.iter().map(Into::into))
+            .collect();
+        let transfers = block_transfers(data);
+        let transfer_rows: Vec<TokenTransferRow> = transfers.iter().map(Into::into).collect();
+        let changes = balance_changes(&block, &transfers);
+
+        self.conn.transaction(|conn| {
+            let inserted = diesel::insert_into(blocks::table)
+                .values(&block)
+                .on_conflict(blocks::hash)
+                .do_nothing()
+                .execute(conn)?;
+
+            for chunk in txs.chunks(CHUNK) {
+                diesel::insert_into(transactions::table)
+                    .values(chunk)
+                    .on_conflict(transactions::hash)
+                    .do_update()
+                    .set((
+                        transactions::block_hash.eq(excluded(transactions::block_hash)),
+                        transactions::block_number.eq(excluded(transactions::block_number)),
+                        transactions::transaction_index
+                            .eq(excluded(transactions::transaction_index)),
+                        transactions::gas_price.eq(excluded(transactions::gas_price)),
+                    ))
+                    .execute(conn)?;
+            }
+
+            for chunk in receipts.chunks(CHUNK) {
+                diesel::insert_into(receipts::table)
+                    .values(chunk)
+                    .on_conflict(receipts::transaction_hash)
+                    .do_update()
+                    .set((
+                        receipts::block_hash.eq(excluded(receipts::block_hash)),
+                        receipts::block_number.eq(excluded(receipts::block_number)),
+                        receipts::status.eq(excluded(receipts::status)),
+                        receipts::gas_used.eq(excluded(receipts::gas_used)),
+                        receipts::cumulative_gas_used.eq(excluded(receipts::cumulative_gas_used)),
+                        receipts::effective_gas_price.eq(excluded(receipts::effective_gas_price)),
+                        receipts::contract_address.eq(excluded(receipts::contract_address)),
+                    ))
+                    .execute(conn)?;
+            }
+
+            for chunk in logs.chunks(CHUNK) {
+                diesel::insert_into(logs::table)
+                    .values(chunk)
+                    .on_conflict((logs::block_hash, logs::log_index))
+                    .do_nothing()
+                    .execute(conn)?;
+            }
+
+            for chunk in transfer_rows.chunks(CHUNK) {
+                diesel::insert_into(token_transfers::table)
+                    .values(chunk)
+                    .on_conflict((
+                        token_transfers::block_hash,
+                        token_transfers::log_index,
+                        token_transfers::batch_index,
+                    ))
+                    .do_nothing()
+                    .execute(conn)?;
+            }
+
+            // A block already stored has already moved the balances.
+            if inserted > 0 {
+                apply_balance_changes(conn, &changes)?;
+            }
+
+            Ok::<_, DwatError>(())
+        })
+    }
+}
+
+/// PgSink's schema holds EVM data only.
+fn unsupported(chain: Chain) -> DwatError {
+    DwatError::Config(format!("PgSink cannot store {} blocks", chain))
+}
+
+/// Nets the transfers of a block into one change per holder and token,
+/// leaving out the zero address that mints come from and burns go to.
+fn balance_changes(block: &BlockRow, transfers: &[TokenTransfer]) -> Vec<BalanceChangeRow> {
+    let mut deltas: HashMap<(Address, Address, BigDecimal), BigDecimal> = HashMap::new();
+    for transfer in transfers {
+        let token_id = numeric(transfer.token_id.unwrap_or_default());
+        let value = numeric(transfer.value);
+        for (holder, delta) in [(transfer.from, -value.clone()), (transfer.to, value)] {
+            if holder.is_zero() {
+                continue;
+            }
+            *deltas
+                .entry((holder, transfer.token, token_id.clone()))
+                .or_default() += delta;
+        }
+    }
+
+    deltas
+        .into_iter()
+        .filter(|(_, delta)| !delta.is_zero())
+        .map(|((holder, token, token_id), delta)| BalanceChangeRow {
+            block_hash: block.hash.clone(),
+            block_number: block.number,
+            holder: hex(holder),
+            token: hex(token),
+            token_id,
+            delta,
+        })
+        .collect()
+}
+
+fn apply_balance_changes(
+    conn: &mut PgConnection,
+    changes: &[BalanceChangeRow],
+) -> Result<(), DwatError> {
+    for chunk in changes.chunks(CHUNK) {
+        diesel::insert_into(token_balance_changes::table)
+            .values(chunk)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        let balances: Vec<TokenBalanceRow> = chunk
+            .iter()
+            .map(|change| TokenBalanceRow {
+                holder: change.holder.clone(),
+                token: change.token.clone(),
+                token_id: change.token_id.clone(),
+                balance: change.delta.clone(),
+                block_number: change.block_number,
+            })
+            .collect();
+        diesel::insert_into(token_balances::table)
+            .values(&balances)
+            .on_conflict((
+                token_balances::holder,
+                token_balances::token,
+                token_balances::token_id,
+            ))
+            .do_update()
+            .set((
+                token_balances::balance
+                    .eq(token_balances::balance + excluded(token_balances::balance)),
+                token_balances::block_number.eq(greatest(
+                    token_balances::block_number,
+                    excluded(token_balances::block_number),
+                )),
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Rebuilds one running balance from the stored balance changes.
+fn recompute_balance(
+    conn: &mut PgConnection,
+    holder: &str,
+    token: &str,
+    token_id: &BigDecimal,
+) -> Result<(), DwatError> {
+    let key = token_balances::table
+        .filter(token_balances::holder.eq(holder))
+        .filter(token_balances::token.eq(token))
+        .filter(token_balances::token_id.eq(token_id));
+    let changes = token_balance_changes::table
+        .filter(token_balance_changes::holder.eq(holder))
+        .filter(token_balance_changes::token.eq(token))
+        .filter(token_balance_changes::token_id.eq(token_id));
+
+    let (balance, last): (Option<BigDecimal>, Option<i64>) = changes
+        .select((
+            diesel::dsl::sum(token_balance_changes::delta),
+            diesel::dsl::max(token_balance_changes::block_number),
+        ))
+        .first(conn)?;
+
+    match (balance, last) {
+        (Some(balance), Some(block_number)) => {
+            diesel::update(key)
+                .set((
+                    token_balances::balance.eq(balance),
+                    token_balances::block_number.eq(block_number),
+                ))
+                .execute(conn)?;
+        }
+        _ => {
+            diesel::delete(key).execute(conn)?;
+        }
+    }
+    Ok(())
+}
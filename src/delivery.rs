@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use ethers::{
+    core::types::{Block, BlockNumber, H256},
+    providers::{JsonRpcClient, Middleware, Provider},
+};
+
+use crate::error::DwatError;
+use crate::reorg::ChainEvent;
+
+/// When a canonical block is handed to the consumer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeliveryMode {
+    /// As soon as it becomes the head.
+    #[default]
+    Latest,
+    /// Once this many blocks have been built on top of it.
+    Confirmations(u64),
+    /// Once the node reports it at or below the `safe` tag.
+    Safe,
+    /// Once the node reports it at or below the `finalized` tag.
+    Finalized,
+}
+
+impl FromStr for DeliveryMode {
+    type Err = DwatError;
+
+    /// Parses `latest`, `safe`, `finalized` or `confirmations:<n>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(DeliveryMode::Latest),
+            "safe" => Ok(DeliveryMode::Safe),
+            "finalized" => Ok(DeliveryMode::Finalized),
+            _ => s
+                .strip_prefix("confirmations:")
+                .and_then(|n| n.parse().ok())
+                .map(DeliveryMode::Confirmations)
+                .ok_or_else(|| DwatError::Config(format!("unknown delivery mode: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for DeliveryMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryMode::Latest => write!(f, "latest"),
+            DeliveryMode::Confirmations(n) => write!(f, "confirmations:{}", n),
+            DeliveryMode::Safe => write!(f, "safe"),
+            DeliveryMode::Finalized => write!(f, "finalized"),
+        }
+    }
+}
+
+/// Holds applied blocks back until they meet the [`DeliveryMode`] threshold.
+///
+/// Blocks reverted while still buffered are dropped without the consumer
+/// ever seeing them. A revert of an already released block is passed
+/// through, which only happens when a reorganisation is deeper than the
+/// chosen threshold.
+#[derive(Debug)]
+pub struct DeliveryGate {
+    mode: DeliveryMode,
+    pending: VecDeque<Block<H256>>,
+}
+
+impl DeliveryGate {
+    pub fn new(mode: DeliveryMode) -> Self {
+        Self {
+            mode,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Feeds one chain event through the gate, returning the events that
+    /// are ready right away. Applied blocks are buffered unless the mode is
+    /// [`DeliveryMode::Latest`]; call [`release`](Self::release) afterwards.
+    pub fn push(&mut self, event: ChainEvent) -> Vec<ChainEvent> {
+        if self.mode == DeliveryMode::Latest {
+            return vec![event];
+        }
+
+        match event {
+            ChainEvent::Applied(block) => {
+                self.pending.push_back(block);
+                Vec::new()
+            }
+            ChainEvent::Reverted(block) => {
+                let before = self.pending.len();
+                self.pending.retain(|pending| pending.hash != block.hash);
+                if self.pending.len() == before {
+                    vec![ChainEvent::Reverted(block)]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    /// Returns the buffered blocks that now meet the threshold, oldest first.
+    pub async fn release<P: JsonRpcClient>(
+        &mut self,
+        provider: &Provider<P>,
+    ) -> Result<Vec<ChainEvent>, DwatError> {
+        let Some(head) = self.pending.back().map(number) else {
+            return Ok(Vec::new());
+        };
+
+        let threshold = match self.mode {
+            DeliveryMode::Latest => head,
+            DeliveryMode::Confirmations(n) => match head.checked_sub(n) {
+                Some(threshold) => threshold,
+                None => return Ok(Vec::new()),
+            },
+            DeliveryMode::Safe => tagged(provider, BlockNumber::Safe).await?,
+            DeliveryMode::Finalized => tagged(provider, BlockNumber::Finalized).await?,
+        };
+
+        let mut ready = Vec::new();
+        while self
+            .pending
+            .front()
+            .is_some_and(|block| number(block) <= threshold)
+        {
+            ready.extend(self.pending.pop_front().map(ChainEvent::Applied));
+        }
+        Ok(ready)
+    }
+}
+
+/// Number of the block the node currently reports under `tag`.
+async fn tagged<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tag: BlockNumber,
+) -> Result<u64, DwatError> {
+    let block = provider
+        .get_block(tag)
+        .await?
+        .ok_or_else(|| DwatError::Config(format!("node does not support the {} tag", tag)))?;
+    Ok(number(&block))
+}
+
+fn number(block: &Block<H256>) -> u64 {
+    block.number.map(|n| n.as_u64()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::core::types::U64;
+
+    use super::*;
+
+    fn block(number: u64) -> Block<H256> {
+        Block {
+            hash: Some(H256::from_low_u64_be(number)),
+            number: Some(U64::from(number)),
+            ..Default::default()
+        }
+    }
+
+    fn numbers(events: &[ChainEvent]) -> Vec<(bool, u64)> {
+        events
+            .iter()
+            .map(|event| {
+                let applied = matches!(event, ChainEvent::Applied(_));
+                (applied, number(event.block()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_delivery_modes() {
+        assert_eq!(
+            "latest".parse::<DeliveryMode>().unwrap(),
+            DeliveryMode::Latest
+        );
+        assert_eq!("safe".parse::<DeliveryMode>().unwrap(), DeliveryMode::Safe);
+        assert_eq!(
+            "finalized".parse::<DeliveryMode>().unwrap(),
+            DeliveryMode::Finalized
+        );
+        assert_eq!(
+            "confirmations:12".parse::<DeliveryMode>().unwrap(),
+            DeliveryMode::Confirmations(12)
+        );
+        for invalid in [
+            "",
+            "Latest",
+            "confirmations",
+            "confirmations:",
+            "confirmations:-1",
+        ] {
+            assert!(invalid.parse::<DeliveryMode>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for mode in [
+            DeliveryMode::Latest,
+            DeliveryMode::Confirmations(3),
+            DeliveryMode::Safe,
+            DeliveryMode::Finalized,
+        ] {
+            assert_eq!(mode.to_string().parse::<DeliveryMode>().unwrap(), mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn latest_passes_events_through() {
+        let (provider, _) = Provider::mocked();
+        let mut gate = DeliveryGate::new(DeliveryMode::Latest);
+
+        let events = gate.push(ChainEvent::Applied(block(1)));
+        assert_eq!(numbers(&events), [(true, 1)]);
+        let events = gate.push(ChainEvent::Reverted(block(1)));
+        assert_eq!(numbers(&events), [(false, 1)]);
+        assert!(gate.release(&provider).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn releases_blocks_once_confirmed() {
+        let (provider, _) = Provider::mocked();
+        let mut gate = DeliveryGate::new(DeliveryMode::Confirmations(2));
+
+        for n in 1..=3 {
+            assert!(gate.push(ChainEvent::Applied(block(n))).is_empty());
+        }
+        assert_eq!(
+            numbers(&gate.release(&provider).await.unwrap()),
+            [(true, 1)]
+        );
+        assert!(gate.release(&provider).await.unwrap().is_empty());
+
+        gate.push(ChainEvent::Applied(block(4)));
+        gate.push(ChainEvent::Applied(block(5)));
+        assert_eq!(
+            numbers(&gate.release(&provider).await.unwrap()),
+            [(true, 2), (true, 3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn drops_blocks_reverted_before_release() {
+        let (provider, _) = Provider::mocked();
+        let mut gate = DeliveryGate::new(DeliveryMode::Confirmations(1));
+
+        gate.push(ChainEvent::Applied(block(1)));
+        assert_eq!(numbers(&gate.release(&provider).await.unwrap()), []);
+        gate.push(ChainEvent::Applied(block(2)));
+        assert!(gate.push(ChainEvent::Reverted(block(2))).is_empty());
+        assert_eq!(numbers(&gate.push(ChainEvent::Reverted(block(1)))), []);
+
+        // Nothing is left to release, and a revert of a block no longer
+        // buffered is passed through.
+        assert!(gate.release(&provider).await.unwrap().is_empty());
+        let events = gate.push(ChainEvent::Reverted(block(0)));
+        assert_eq!(numbers(&events), [(false, 0)]);
+    }
+
+    #[tokio::test]
+    async fn releases_up_to_the_finalized_tag() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(block(2)).unwrap();
+        let mut gate = DeliveryGate::new(DeliveryMode::Finalized);
+
+        for n in 1..=4 {
+            gate.push(ChainEvent::Applied(block(n)));
+        }
+        assert_eq!(
+            numbers(&gate.release(&provider).await.unwrap()),
+            [(true, 1), (true, 2)]
+        );
+    }
+}
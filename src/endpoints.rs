@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+
+use ethers::{
+    core::types::H256,
+    providers::{Middleware, Provider},
+};
+use futures::future::join_all;
+use tokio::time;
+
+use crate::config::StreamConfig;
+use crate::error::DwatError;
+use crate::transport::Transport;
+
+/// Weight of the latest outcome in an endpoint's reliability score.
+const SMOOTHING: f64 = 0.3;
+
+/// How long a quorum peer gets to answer for a block.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A set of interchangeable WebSocket, HTTP or IPC endpoints ranked by
+/// health.
+///
+/// Each endpoint keeps a reliability score (an exponential average of
+/// successes and failures) and a smoothed response time. [`connect`] tries
+/// endpoints best first, so a failing provider is skipped in favour of a
+/// healthy one and retried once the others degrade.
+///
+/// [`connect`]: EndpointPool::connect
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+}
+
+struct Endpoint {
+    url: String,
+    /// Open connection, kept for quorum checks.
+    provider: Option<Provider<Transport>>,
+    reliability: f64,
+    latency: Option<Duration>,
+}
+
+impl Endpoint {
+    fn succeeded(&mut self, elapsed: Duration) {
+        self.reliability += SMOOTHING * (1.0 - self.reliability);
+        self.latency = Some(match self.latency {
+            Some(latency) => latency.mul_f64(1.0 - SMOOTHING) + elapsed.mul_f64(SMOOTHING),
+            None => elapsed,
+        });
+    }
+
+    fn failed(&mut self) {
+        self.reliability -= SMOOTHING * self.reliability;
+        self.provider = None;
+    }
+}
+
+impl EndpointPool {
+    pub fn new(urls: impl IntoIterator<Item = String>) -> Self {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                url,
+                provider: None,
+                reliability: 1.0,
+                latency: None,
+            })
+            .collect();
+        Self { endpoints }
+    }
+
+    /// Pools `config.url` and `config.fallbacks`.
+    pub fn from_config(config: &StreamConfig) -> Self {
+        Self::new(config.endpoints().map(String::from))
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.endpoints[index].url
+    }
+
+    /// Endpoint indices, healthiest first.
+    pub fn ranked(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.endpoints.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.endpoints[a], &self.endpoints[b]);
+            b.reliability.total_cmp(&a.reliability).then(
+                a.latency
+                    .unwrap_or_default()
+                    .cmp(&b.latency.unwrap_or_default()),
+            )
+        });
+        order
+    }
+
+    /// Connects to the healthiest reachable endpoint, returning its index
+    /// and provider, or the last error if none could be reached.
+    pub async fn connect(&mut self) -> Result<(usize, Provider<Transport>), DwatError> {
+        let mut last = DwatError::Config("no endpoints configured".into());
+        for index in self.ranked() {
+            match self.provider(index).await {
+                Ok(provider) => return Ok((index, provider)),
+                Err(err) => last = err,
+            }
+        }
+        Err(last)
+    }
+
+    /// Records a failure of the endpoint at `index`, dropping its connection.
+    pub fn failed(&mut self, index: usize) {
+        self.endpoints[index].failed();
+    }
+
+    /// Returns whether at least `quorum` endpoints, counting `primary`,
+    /// report `hash` as block `number`.
+    pub async fn agree(&mut self, primary: usize, number: u64, hash: H256, quorum: usize) -> bool {
+        let peers: Vec<_> = self
+            .ranked()
+            .into_iter()
+            .filter(|&index| index != primary)
+            .map(|index| {
+                let endpoint = &self.endpoints[index];
+                (index, endpoint.provider.clone(), endpoint.url.clone())
+            })
+            .collect();
+
+        // Peers without an open connection are dialled within the timeout.
+        let answers = join_all(peers.into_iter().map(|(index, provider, url)| async move {
+            let start = Instant::now();
+            let answer = time::timeout(PEER_TIMEOUT, async {
+                let provider = match provider {
+                    Some(provider) => provider,
+                    None => Transport::provider(&url).await?,
+                };
+                let block = provider.get_block(number).await?;
+                Ok::<_, DwatError>((provider, block))
+            })
+            .await;
+            (index, answer, start.elapsed())
+        }))
+        .await;
+
+        let mut agreed = 1;
+        for (index, answer, elapsed) in answers {
+            let endpoint = &mut self.endpoints[index];
+            match answer {
+                Ok(Ok((provider, block))) => {
+                    endpoint.succeeded(elapsed);
+                    endpoint.provider = Some(provider);
+                    if block.and_then(|block| block.hash) == Some(hash) {
+                        agreed += 1;
+                    }
+                }
+                _ => endpoint.failed(),
+            }
+        }
+        agreed >= quorum
+    }
+
+    /// Returns the open connection to `index`, connecting if needed.
+    async fn provider(&mut self, index: usize) -> Result<Provider<Transport>, DwatError> {
+        let endpoint = &mut self.endpoints[index];
+        if let Some(provider) = &endpoint.provider {
+            return Ok(provider.clone());
+        }
+
+        let start = Instant::now();
+        match Transport::provider(&endpoint.url).await {
+            Ok(provider) => {
+                endpoint.succeeded(start.elapsed());
+                endpoint.provider = Some(provider.clone());
+                Ok(provider)
+            }
+            Err(err) => {
+                endpoint.failed();
+                Err(err)
+            }
+        }
+    }
+}
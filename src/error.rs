@@ -0,0 +1,108 @@
+use std::fmt;
+
+use ethers::{core::types::H256, providers::ProviderError};
+
+/// Errors surfaced by the `dwat` library.
+#[derive(Debug)]
+pub enum DwatError {
+    /// A required setting is missing or malformed.
+    Config(String),
+    /// The JSON-RPC provider returned an error.
+    Provider(ProviderError),
+    /// The upstream subscription ended.
+    SubscriptionClosed,
+    /// The node did not return a block it should have.
+    MissingBlock(u64),
+    /// The node did not return the block with this hash.
+    UnknownBlock(H256),
+    /// A reorganisation went deeper than the tracked window.
+    DeepReorg(usize),
+    /// Too few endpoints agreed on the hash of this block.
+    NoQuorum(u64),
+    /// The node did not return the transaction or receipt with this hash.
+    UnknownTransaction(H256),
+    /// Connecting to the database failed.
+    DatabaseConnection(diesel::ConnectionError),
+    /// A database query failed.
+    Database(diesel::result::Error),
+    /// Applying the embedded migrations failed.
+    Migration(String),
+    /// Reading or writing a checkpoint failed.
+    Checkpoint(String),
+    /// An ABI could not be loaded or applied.
+    Abi(String),
+    /// A contract call failed or returned something unexpected.
+    Contract(String),
+    /// A Solana RPC or PubSub request failed.
+    Solana(String),
+    /// An Anchor IDL could not be loaded or applied.
+    Idl(String),
+}
+
+impl fmt::Display for DwatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DwatError::Config(msg) => write!(f, "configuration error: {}", msg),
+            DwatError::Provider(err) => write!(f, "provider error: {}", err),
+            DwatError::SubscriptionClosed => write!(f, "subscription closed by the node"),
+            DwatError::MissingBlock(number) => write!(f, "node has no block {}", number),
+            DwatError::UnknownBlock(hash) => write!(f, "node has no block {:?}", hash),
+            DwatError::DeepReorg(depth) => {
+                write!(f, "reorganisation deeper than the {} tracked blocks", depth)
+            }
+            DwatError::NoQuorum(number) => {
+                write!(f, "endpoints did not reach quorum on block {}", number)
+            }
+            DwatError::UnknownTransaction(hash) => write!(f, "node has no transaction {:?}", hash),
+            DwatError::DatabaseConnection(err) => write!(f, "database connection error: {}", err),
+            DwatError::Database(err) => write!(f, "database error: {}", err),
+            DwatError::Migration(msg) => write!(f, "migration error: {}", msg),
+            DwatError::Checkpoint(msg) => write!(f, "checkpoint error: {}", msg),
+            DwatError::Abi(msg) => write!(f, "ABI error: {}", msg),
+            DwatError::Contract(msg) => write!(f, "contract error: {}", msg),
+            DwatError::Solana(msg) => write!(f, "Solana error: {}", msg),
+            DwatError::Idl(msg) => write!(f, "IDL error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DwatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DwatError::Provider(err) => Some(err),
+            DwatError::DatabaseConnection(err) => Some(err),
+            DwatError::Database(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProviderError> for DwatError {
+    fn from(err: ProviderError) -> Self {
+        DwatError::Provider(err)
+    }
+}
+
+impl From<diesel::ConnectionError> for DwatError {
+    fn from(err: diesel::ConnectionError) -> Self {
+        DwatError::DatabaseConnection(err)
+    }
+}
+
+impl From<diesel::result::Error> for DwatError {
+    fn from(err: diesel::result::Error) -> Self {
+        DwatError::Database(err)
+    }
+}
+
+impl From<solana_client::client_error::ClientError> for DwatError {
+    fn from(err: solana_client::client_error::ClientError) -> Self {
+        DwatError::Solana(err.to_string())
+    }
+}
+
+impl From<solana_client::pubsub_client::PubsubClientError> for DwatError {
+    fn from(err: solana_client::pubsub_client::PubsubClientError) -> Self {
+        DwatError::Solana(err.to_string())
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use actix_web::{get, rt, web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_ws::{Message, MessageStream, Session};
+use ethers::{
+    core::types::{Address, Log, H256},
+    providers::StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::abi::{AbiRegistry, DecodedEvent};
+use crate::error::DwatError;
+use crate::source::{ChainSource, ChainUpdate, Head, NativeBlock};
+
+/// Messages buffered per client before it starts missing them.
+const CLIENT_BUFFER: usize = 1024;
+
+/// A message pushed to feed clients.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    /// A block became canonical.
+    Block(Head),
+    /// A previously announced block was orphaned. Its logs follow again
+    /// with `removed` set, which also retracts the events decoded from them.
+    Reverted(Head),
+    /// A log emitted in a canonical block, or retracted when `removed`.
+    Log(Log),
+    /// A log decoded against a registered ABI.
+    Event(DecodedEvent),
+}
+
+impl FeedMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            FeedMessage::Block(_) => "block",
+            FeedMessage::Reverted(_) => "reverted",
+            FeedMessage::Log(_) => "log",
+            FeedMessage::Event(_) => "event",
+        }
+    }
+
+    /// The emitting contract and first topic, for messages that have them.
+    fn log_fields(&self) -> Option<(Address, Option<H256>)> {
+        match self {
+            FeedMessage::Log(log) => Some((log.address, log.topics.first().copied())),
+            FeedMessage::Event(event) => Some((event.address, Some(event.topic0))),
+            _ => None,
+        }
+    }
+}
+
+/// What a client wants to receive, sent as a JSON text message.
+///
+/// Every field is optional; an empty filter receives everything. `address`
+/// and `topic0` only narrow down messages that carry a log or event.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedFilter {
+    /// Message types, e.g. `["block", "log"]`.
+    pub types: Option<Vec<String>>,
+    pub address: Option<Vec<Address>>,
+    pub topic0: Option<Vec<H256>>,
+}
+
+impl FeedFilter {
+    pub fn matches(&self, message: &FeedMessage) -> bool {
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| t == message.kind()) {
+                return false;
+            }
+        }
+
+        let Some((address, topic0)) = message.log_fields() else {
+            return true;
+        };
+        if let Some(addresses) = &self.address {
+            if !addresses.contains(&address) {
+                return false;
+            }
+        }
+        if let Some(topics) = &self.topic0 {
+            if !topic0.is_some_and(|topic| topics.contains(&topic)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Fans one upstream subscription out to any number of clients.
+///
+/// Each client reads from its own bounded queue, so a slow client only
+/// loses its own messages and is told how many it missed.
+#[derive(Debug, Clone)]
+pub struct Hub {
+    tx: broadcast::Sender<Arc<FeedMessage>>,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CLIENT_BUFFER);
+        Self { tx }
+    }
+
+    pub fn publish(&self, message: FeedMessage) {
+        // Sending only fails when nobody is listening.
+        let _ = self.tx.send(Arc::new(message));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<FeedMessage>> {
+        self.tx.subscribe()
+    }
+}
+
+/// Follows the canonical blocks of `source` and publishes blocks and
+/// reverts to `hub`. EVM blocks are followed by their logs and the events
+/// `registry` can decode. The logs of the last `reorg_depth` blocks are
+/// kept, so that a revert is followed by its block's logs flagged
+/// `removed`, newest first.
+pub async fn run<C: ChainSource>(
+    source: &C,
+    hub: Hub,
+    registry: Arc<AbiRegistry>,
+    reorg_depth: usize,
+) -> Result<(), DwatError> {
+    let mut stream = source.blocks(None).await?;
+    // Logs published per block hash, oldest block first.
+    let mut recent: VecDeque<(String, Vec<Log>)> = VecDeque::new();
+
+    while let Some(update) = stream.next().await {
+        match update? {
+            ChainUpdate::Applied(block) => {
+                let hash = block.head.hash.clone();
+                hub.publish(FeedMessage::Block(block.head));
+                if let NativeBlock::Evm(data) = block.native {
+                    let events = registry.decode_logs(data.logs());
+                    let logs: Vec<Log> = data.receipts.into_iter().flat_map(|r| r.logs).collect();
+                    for log in &logs {
+                        hub.publish(FeedMessage::Log(log.clone()));
+                    }
+                    for event in events {
+                        hub.publish(FeedMessage::Event(event));
+                    }
+
+                    recent.push_back((hash, logs));
+                    if recent.len() > reorg_depth {
+                        recent.pop_front();
+                    }
+                }
+            }
+            ChainUpdate::Reverted(head) => {
+                let logs = recent
+                    .iter()
+                    .rposition(|(hash, _)| *hash == head.hash)
+                    .and_then(|index| recent.remove(index))
+                    .map(|(_, logs)| logs)
+                    .unwrap_or_default();
+                hub.publish(FeedMessage::Reverted(head));
+                for mut log in logs.into_iter().rev() {
+                    log.removed = Some(true);
+                    hub.publish(FeedMessage::Log(log));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[get("/ws")]
+async fn connect(
+    req: HttpRequest,
+    body: web::Payload,
+    hub: web::Data<Hub>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    rt::spawn(client(session, messages, hub.subscribe()));
+    Ok(response)
+}
+
+/// Forwards matching messages to one client until either side goes away.
+async fn client(
+    mut session: Session,
+    mut messages: MessageStream,
+    mut feed: broadcast::Receiver<Arc<FeedMessage>>,
+) {
+    let mut filter = FeedFilter::default();
+
+    loop {
+        tokio::select! {
+            incoming = messages.next() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(update) => filter = update,
+                    Err(err) => {
+                        let error = serde_json::json!({ "type": "error", "error": err.to_string() });
+                        if session.text(error.to_string()).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Close(reason))) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return,
+            },
+            outgoing = feed.recv() => match outgoing {
+                Ok(message) => {
+                    if !filter.matches(&message) {
+                        continue;
+                    }
+                    let Ok(json) = serde_json::to_string(&*message) else {
+                        continue;
+                    };
+                    if session.text(json).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let lagged = serde_json::json!({ "type": "lagged", "skipped": skipped });
+                    if session.text(lagged.to_string()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Closed) => {
+                    let _ = session.close(None).await;
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// Registers the `/ws` feed endpoint on an actix-web app.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(connect);
+}
+
+/// Serves the feed on `bind` until the server is stopped.
+pub async fn serve(bind: &str, hub: Hub) -> std::io::Result<()> {
+    let hub = web::Data::new(hub);
+    HttpServer::new(move || App::new().app_data(hub.clone()).configure(configure))
+        .bind(bind)?
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use ethers::core::types::{Block, TransactionReceipt, U256};
+    use futures::stream::{self, BoxStream};
+
+    use super::*;
+    use crate::block::BlockData;
+    use crate::checkpoint::Checkpoint;
+    use crate::source::{Chain, ChainBlock, ChainTransaction};
+
+    /// Replays a fixed list of updates.
+    struct Replay(Vec<ChainUpdate>);
+
+    #[async_trait]
+    impl ChainSource for Replay {
+        type Config = Vec<ChainUpdate>;
+
+        async fn connect(updates: Vec<ChainUpdate>) -> Result<Self, DwatError> {
+            Ok(Self(updates))
+        }
+
+        fn chain(&self) -> Chain {
+            Chain::Evm
+        }
+
+        async fn heads(&self) -> Result<BoxStream<'static, Result<Head, DwatError>>, DwatError> {
+            Ok(stream::empty().boxed())
+        }
+
+        async fn blocks(
+            &self,
+            _: Option<Checkpoint>,
+        ) -> Result<BoxStream<'static, Result<ChainUpdate, DwatError>>, DwatError> {
+            Ok(stream::iter(self.0.clone().into_iter().map(Ok)).boxed())
+        }
+
+        async fn fetch_range(&self, _: u64, _: u64) -> Result<Vec<ChainBlock>, DwatError> {
+            Ok(Vec::new())
+        }
+
+        async fn fetch_transaction(&self, _: &str) -> Result<Option<ChainTransaction>, DwatError> {
+            Ok(None)
+        }
+    }
+
+    fn block(number: u64, logs: u64) -> ChainBlock {
+        let hash = H256::from_low_u64_be(number);
+        let receipt = TransactionReceipt {
+            logs: (0..logs)
+                .map(|index| Log {
+                    block_hash: Some(hash),
+                    log_index: Some(U256::from(index)),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        ChainBlock::from(BlockData {
+            block: Block {
+                hash: Some(hash),
+                number: Some(number.into()),
+                ..Default::default()
+            },
+            receipts: vec![receipt],
+        })
+    }
+
+    #[tokio::test]
+    async fn retracts_the_logs_of_a_reverted_block() {
+        let (first, second) = (block(1, 1), block(2, 2));
+        let reverted = second.head.clone();
+        let source = Replay::connect(vec![
+            ChainUpdate::Applied(first),
+            ChainUpdate::Applied(second),
+            ChainUpdate::Reverted(reverted),
+        ])
+        .await
+        .unwrap();
+        let hub = Hub::new();
+        let mut feed = hub.subscribe();
+
+        run(&source, hub, Arc::new(AbiRegistry::new()), 64)
+            .await
+            .unwrap();
+
+        let mut messages = Vec::new();
+        while let Ok(message) = feed.try_recv() {
+            messages.push(match &*message {
+                FeedMessage::Log(log) => format!(
+                    "log {} {}{}",
+                    log.block_hash.unwrap().to_low_u64_be(),
+                    log.log_index.unwrap(),
+                    if log.removed == Some(true) {
+                        " removed"
+                    } else {
+                        ""
+                    }
+                ),
+                message => message.kind().to_string(),
+            });
+        }
+        assert_eq!(
+            messages,
+            [
+                "block",
+                "log 1 0",
+                "block",
+                "log 2 0",
+                "log 2 1",
+                "reverted",
+                "log 2 1 removed",
+                "log 2 0 removed",
+            ]
+        );
+    }
+}
@@ -1,35 +1,376 @@
-use ethers::{
-    core::{
-        abi::Abi,
-        types::{Address, U256, H256, I256},
-    },
-    providers::{Provider, Ws, Middleware},
-    contract::{Contract, Event},
-    prelude::*,
-};
-use std::sync::Arc;
-use std::str::FromStr;
-use tokio;
-use eyre;
 use std::env;
+use std::sync::Arc;
+
+use ethers::core::types::Address;
+use ethers::providers::{Middleware, StreamExt};
 
-// pub mod swap;
-// use swap::entry_point;
+pub mod abi;
+pub mod api;
+pub mod backfill;
+pub mod block;
+pub mod checkpoint;
+pub mod config;
+pub mod db;
+pub mod delivery;
+pub mod endpoints;
+pub mod error;
+pub mod feed;
+pub mod logs;
+pub mod mempool;
+pub mod models;
+pub mod queries;
+pub mod reconnect;
+pub mod reorg;
+pub mod schema;
+pub mod selectors;
+pub mod sink;
+pub mod solana;
+pub mod source;
+pub mod stream;
+pub mod swap;
+pub mod tokens;
+pub mod transfers;
+pub mod transport;
 
-pub async fn read() -> eyre::Result<()> {
+pub use abi::{AbiRegistry, CallInput, DecodedCall, DecodedEvent};
+pub use backfill::BackfillConfig;
+pub use block::{BlockData, BlockFetcher};
+pub use checkpoint::{Checkpoint, CheckpointStore, FileCheckpoint, PgCheckpoint};
+pub use config::StreamConfig;
+pub use db::PgSink;
+pub use delivery::{DeliveryGate, DeliveryMode};
+pub use endpoints::EndpointPool;
+pub use error::DwatError;
+pub use logs::{LogEvent, LogStream};
+pub use mempool::{MempoolConfig, MempoolEvent, MempoolFilter, MempoolStream};
+pub use reconnect::ReconnectPolicy;
+pub use reorg::{ChainEvent, ReorgTracker};
+pub use sink::Sink;
+pub use solana::{
+    IdlRegistry, SolanaBlock, SolanaBlockStream, SolanaConfig, SolanaEvent, WatchStream,
+};
+pub use source::{Chain, ChainBlock, ChainSource, ChainTransaction, EvmSource, Head, SolanaSource};
+pub use stream::{BlockStream, ChainStream, FullBlockStream, FullEvent};
+pub use swap::entry_point;
+pub use tokens::{TokenMetadata, TokenResolver};
+pub use transfers::{TokenStandard, TokenTransfer};
+pub use transport::Transport;
+
+/// Prints every new block from `WS_ENDPOINT` until the subscription ends.
+///
+/// With `full` set, each canonical block is printed as a JSON record with
+/// its transactions and receipts instead, plus every transaction's input
+/// decoded against the ABIs in `ABI_DIR` or the bundled signatures.
+pub async fn read(full: bool) -> eyre::Result<()> {
     dotenv::dotenv().ok();
 
-    let ws_url = env::var("WS_ENDPOINT")
-        .expect("WS_ENDPOINT must be set in environment");
-    // println!("{}", ws_url);
+    if full {
+        let registry = registry_from_env()?;
+        let mut stream = FullBlockStream::from_env().await?;
+        while let Some(event) = stream.next().await {
+            let event = event?;
+            let mut line = serde_json::to_value(&event)?;
+            if let FullEvent::Applied(data) = &event {
+                line["calls"] =
+                    serde_json::to_value(registry.decode_calls(&data.block.transactions))?;
+            }
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let mut stream = BlockStream::from_env().await?;
 
-    let provider = Provider::<Ws>::connect(&ws_url).await?;
-    let mut stream = provider.subscribe_blocks().await?;
-    
     while let Some(block) = stream.next().await {
-        // println!("{:?}", block.hash);
-        println!("{:?}", block)
+        println!("{:?}", block?)
     }
-    
+
+    Ok(())
+}
+
+/// Prints every log matching `LOG_ADDRESS` and `LOG_TOPIC0` from
+/// `WS_ENDPOINT` as a JSON line, including removals.
+pub async fn logs() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let mut stream = LogStream::from_env().await?;
+    while let Some(event) = stream.next().await {
+        println!("{}", serde_json::to_string(&event?)?);
+    }
+
+    Ok(())
+}
+
+/// Prints the lifecycle of pending transactions matching the `MEMPOOL_*`
+/// filters as JSON lines.
+pub async fn mempool() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let mut stream = MempoolStream::from_env().await?;
+    while let Some(event) = stream.next().await {
+        println!("{}", serde_json::to_string(&event?)?);
+    }
+
+    Ok(())
+}
+
+/// Prints every Solana block from `SOLANA_RPC_ENDPOINT` at
+/// `SOLANA_COMMITMENT` as a JSON line.
+pub async fn solana() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let mut stream = SolanaBlockStream::from_env().await?;
+    while let Some(block) = stream.next().await {
+        println!("{}", serde_json::to_string(&block?)?);
+    }
+
+    Ok(())
+}
+
+/// Prints the SPL Token transfers, mints, burns and approvals in new Solana
+/// blocks as JSON lines.
+pub async fn solana_tokens() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let mut stream = SolanaBlockStream::from_env().await?;
+    while let Some(block) = stream.next().await {
+        for event in solana::token_events(&block?) {
+            println!("{}", serde_json::to_string(&event)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the instructions and events of programs with an IDL in `IDL_DIR`
+/// as JSON lines.
+pub async fn solana_idl() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let registry = idl_registry_from_env()?;
+    let mut stream = SolanaBlockStream::from_env().await?;
+    while let Some(block) = stream.next().await {
+        for decoded in registry.decode_block(&block?) {
+            println!("{}", serde_json::to_string(&decoded)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints changes to `SOLANA_ACCOUNTS` and the logs of transactions
+/// mentioning `SOLANA_MENTIONS` as JSON lines.
+pub async fn solana_watch() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let mut stream = WatchStream::from_env().await?;
+    while let Some(event) = stream.next().await {
+        println!("{}", serde_json::to_string(&event?)?);
+    }
+
+    Ok(())
+}
+
+/// Prints new heads of `chain` as normalised JSON lines, reading the
+/// endpoints from the environment.
+pub async fn heads(chain: Chain) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    match chain {
+        Chain::Evm => print_heads(EvmSource::from_env().await?).await,
+        Chain::Solana => print_heads(SolanaSource::from_env().await?).await,
+    }
+}
+
+async fn print_heads<S: ChainSource>(source: S) -> eyre::Result<()> {
+    let mut heads = source.heads().await?;
+    while let Some(head) = heads.next().await {
+        println!("{}", serde_json::to_string(&head?)?);
+    }
+
+    Ok(())
+}
+
+/// Indexes the chain from `WS_ENDPOINT` into the Postgres database at
+/// `DATABASE_URL`, resuming from the last checkpoint.
+///
+/// Progress is kept in the file named by `CHECKPOINT_FILE` if set, and in
+/// the database otherwise.
+pub async fn index() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let config = StreamConfig::from_env()?;
+    let url = db::database_url()?;
+    let mut sink = PgSink::connect(&url)?;
+    let mut checkpoints: Box<dyn CheckpointStore> = match env::var("CHECKPOINT_FILE") {
+        Ok(path) => Box::new(FileCheckpoint::new(path)),
+        Err(_) => Box::new(PgCheckpoint::connect(&url, "live")?),
+    };
+
+    let reorg_depth = config.reorg_depth;
+    let source = EvmSource::connect(config).await?;
+    sink::index(&source, &mut sink, checkpoints.as_mut(), reorg_depth).await?;
+
+    Ok(())
+}
+
+/// Indexes the blocks in `config`'s range from `WS_ENDPOINT` into the
+/// Postgres database at `DATABASE_URL`.
+pub async fn backfill(config: BackfillConfig) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let stream = StreamConfig::from_env()?;
+    let provider = Transport::provider(&stream.url).await?;
+    let mut sink = PgSink::from_env()?;
+
+    backfill::backfill(&provider, &config, &mut sink).await?;
+
+    Ok(())
+}
+
+/// Serves the REST query API over the database at `DATABASE_URL` on the
+/// address in `API_BIND`.
+pub async fn serve() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let bind = env::var("API_BIND").unwrap_or_else(|_| "127.0.0.1:8080".into());
+    let pool = db::pool(&db::database_url()?)?;
+    api::serve(&bind, pool).await?;
+
+    Ok(())
+}
+
+/// Follows `chain` and pushes blocks and reverts, plus the logs of EVM
+/// blocks and the events decoded from them with the ABIs in `ABI_DIR`, to
+/// WebSocket clients connected to `/ws` on the address in `FEED_BIND`.
+pub async fn feed(chain: Chain) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    match chain {
+        Chain::Evm => {
+            let config = StreamConfig::from_env()?;
+            let reorg_depth = config.reorg_depth;
+            serve_feed(EvmSource::connect(config).await?, reorg_depth).await
+        }
+        // Solana blocks have no logs to retract.
+        Chain::Solana => serve_feed(SolanaSource::from_env().await?, 0).await,
+    }
+}
+
+async fn serve_feed<S: ChainSource>(source: S, reorg_depth: usize) -> eyre::Result<()> {
+    let bind = env::var("FEED_BIND").unwrap_or_else(|_| "127.0.0.1:8081".into());
+    let registry = Arc::new(registry_from_env()?);
+    let hub = feed::Hub::new();
+
+    tokio::select! {
+        result = feed::run(&source, hub.clone(), registry, reorg_depth) => result?,
+        result = feed::serve(&bind, hub) => result?,
+    }
+
+    Ok(())
+}
+
+/// Loads the ABIs in `ABI_DIR`, or an empty registry when it is unset.
+pub fn registry_from_env() -> Result<AbiRegistry, DwatError> {
+    match env::var("ABI_DIR") {
+        Ok(dir) => AbiRegistry::load_dir(dir),
+        Err(_) => Ok(AbiRegistry::new()),
+    }
+}
+
+/// Loads the Anchor IDLs in `IDL_DIR`, or an empty registry when it is unset.
+pub fn idl_registry_from_env() -> Result<IdlRegistry, DwatError> {
+    match env::var("IDL_DIR") {
+        Ok(dir) => IdlRegistry::load_dir(dir),
+        Err(_) => Ok(IdlRegistry::new()),
+    }
+}
+
+/// A token resolver that also caches in Postgres when `DATABASE_URL` is set.
+pub fn token_resolver_from_env<M: Middleware + 'static>(
+    client: Arc<M>,
+) -> Result<TokenResolver<M>, DwatError> {
+    match env::var("DATABASE_URL") {
+        Ok(url) => Ok(TokenResolver::with_pool(client, db::pool(&url)?)),
+        Err(_) => Ok(TokenResolver::new(client)),
+    }
+}
+
+/// Prints every DEX swap in new blocks from `WS_ENDPOINT` as a JSON line.
+pub async fn swaps() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let config = StreamConfig::from_env()?;
+    let provider = Arc::new(Transport::provider(&config.url).await?);
+    let tokens = token_resolver_from_env(provider.clone())?;
+    let mut tracker = swap::SwapTracker::with_resolver(provider, tokens);
+    let mut stream = FullBlockStream::connect_with(config).await?;
+
+    while let Some(event) = stream.next().await {
+        if let FullEvent::Applied(data) = event? {
+            for swap in tracker.swaps_in_block(&data).await? {
+                println!("{}", serde_json::to_string(&swap)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the ERC-20, ERC-721 and ERC-1155 transfers in new blocks from
+/// `WS_ENDPOINT` as JSON lines, with their tokens' symbols and decimals.
+pub async fn token_transfers() -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let config = StreamConfig::from_env()?;
+    let provider = Arc::new(Transport::provider(&config.url).await?);
+    let mut tokens = token_resolver_from_env(provider)?;
+    let mut stream = FullBlockStream::connect_with(config).await?;
+
+    while let Some(event) = stream.next().await {
+        if let FullEvent::Applied(data) = event? {
+            let mut transfers = transfers::block_transfers(&data);
+            tokens.annotate(&mut transfers).await?;
+            for transfer in transfers {
+                println!("{}", serde_json::to_string(&transfer)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the metadata of `address` as JSON, read again from the chain when
+/// `refresh` is set.
+pub async fn token(address: Address, refresh: bool) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let config = StreamConfig::from_env()?;
+    let provider = Arc::new(Transport::provider(&config.url).await?);
+    let mut tokens = token_resolver_from_env(provider)?;
+    let metadata = if refresh {
+        tokens.refresh(address).await?
+    } else {
+        tokens.resolve(address).await?
+    };
+    println!("{}", serde_json::to_string(&metadata)?);
+
+    Ok(())
+}
+
+/// Executes `order` over `WS_ENDPOINT`, signing with `PRIVATE_KEY`, and
+/// prints the outcome as JSON.
+pub async fn execute_swap(order: swap::SwapOrder) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+
+    let config = StreamConfig::from_env()?;
+    let key = env::var("PRIVATE_KEY")
+        .map_err(|_| DwatError::Config("PRIVATE_KEY must be set in environment".into()))?;
+    let provider = Transport::provider(&config.url).await?;
+    let client = Arc::new(swap::execute::signer(provider, &key).await?);
+
+    let execution = swap::entry_point(client, &order).await?;
+    println!("{}", serde_json::to_string_pretty(&execution)?);
+
     Ok(())
-}
\ No newline at end of file
+}
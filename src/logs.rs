@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use ethers::{
+    core::types::{Address, Filter, Log, H256},
+    providers::{Middleware, Provider, StreamExt},
+};
+use futures::{FutureExt, Stream};
+use serde::Serialize;
+use tokio::time;
+
+use crate::config::{parse_list_var, StreamConfig};
+use crate::endpoints::EndpointPool;
+use crate::error::DwatError;
+use crate::reconnect::{Outbox, Supervised};
+use crate::transport::Transport;
+
+/// Blocks covered by one `eth_getLogs` call while catching up.
+const LOG_RANGE: u64 = 2_000;
+
+/// A change to the set of canonical logs matching a filter.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "log", rename_all = "snake_case")]
+pub enum LogEvent {
+    /// The log was emitted in a canonical block.
+    Added(Log),
+    /// A previously added log was orphaned by a reorganisation.
+    Removed(Log),
+}
+
+/// A stream of logs matching a server-side filter.
+///
+/// Built on `eth_subscribe("logs")` with the same guarantees as
+/// [`ChainStream`](crate::ChainStream): the subscription is re-established
+/// with backoff, logs emitted during an outage are fetched with
+/// `eth_getLogs`, repeats are dropped, and logs the node flags as
+/// `removed` — or that belong to blocks found to be orphaned after a
+/// reconnect — are reported as [`LogEvent::Removed`].
+pub struct LogStream {
+    inner: Supervised<LogEvent>,
+}
+
+impl LogStream {
+    pub async fn connect(url: &str, filter: Filter) -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::new(url), filter).await
+    }
+
+    /// Connects to the endpoint configured in the environment with the
+    /// filter from [`filter_from_env`].
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::from_env()?, filter_from_env()?).await
+    }
+
+    /// Subscribes to logs matching `filter`. Block bounds in the filter are
+    /// ignored; `config.resume_from` sets where catching up starts.
+    pub async fn connect_with(config: StreamConfig, filter: Filter) -> Result<Self, DwatError> {
+        let mut pool = EndpointPool::from_config(&config);
+        let (active, provider) = pool.connect().await?;
+
+        let inner = Supervised::spawn(
+            config.reconnect.clone(),
+            |tx| LogSupervisor {
+                synced_to: config.resume_from.map(|checkpoint| checkpoint.number),
+                config,
+                filter,
+                tx,
+                pool,
+                active,
+                window: BTreeMap::new(),
+            },
+            provider,
+            |supervisor| supervisor.reconnect().boxed(),
+            |supervisor, provider| {
+                async move {
+                    let err = supervisor.follow(provider).await;
+                    supervisor.pool.failed(supervisor.active);
+                    err
+                }
+                .boxed()
+            },
+        );
+
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for LogStream {
+    type Item = Result<LogEvent, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Builds a filter from the comma-separated `LOG_ADDRESS` and `LOG_TOPIC0`
+/// variables; either may be unset.
+pub fn filter_from_env() -> Result<Filter, DwatError> {
+    let mut filter = Filter::new();
+    if let Some(addresses) = parse_list_var::<Address>("LOG_ADDRESS")? {
+        filter = filter.address(addresses);
+    }
+    if let Some(topics) = parse_list_var::<H256>("LOG_TOPIC0")? {
+        filter = filter.topic0(topics);
+    }
+    Ok(filter)
+}
+
+/// Logs delivered for one block.
+struct BlockLogs {
+    hash: H256,
+    logs: Vec<Log>,
+}
+
+/// Follows a log subscription for a [`LogStream`].
+struct LogSupervisor {
+    config: StreamConfig,
+    filter: Filter,
+    tx: Outbox<LogEvent>,
+    pool: EndpointPool,
+    /// Index of the endpoint currently followed.
+    active: usize,
+    /// Recently scanned blocks by number with the logs delivered for them,
+    /// to drop repeats and to undo blocks that turn out to be orphaned.
+    window: BTreeMap<u64, BlockLogs>,
+    /// Every block up to this one has been scanned.
+    synced_to: Option<u64>,
+}
+
+impl LogSupervisor {
+    /// Connects to the healthiest endpoint.
+    async fn reconnect(&mut self) -> Result<Provider<Transport>, DwatError> {
+        let (active, provider) = self.pool.connect().await?;
+        self.active = active;
+        Ok(provider)
+    }
+
+    /// Forwards logs until the subscription fails, returning the cause.
+    async fn follow(&mut self, provider: &Provider<Transport>) -> DwatError {
+        if !provider.as_ref().is_pubsub() {
+            return self.poll(provider).await;
+        }
+
+        let mut sub = match provider.subscribe_logs(&self.filter).await {
+            Ok(sub) => sub,
+            Err(err) => return err.into(),
+        };
+
+        if let Err(err) = self.catch_up(provider).await {
+            return err;
+        }
+
+        while let Some(log) = sub.next().await {
+            if let Err(err) = self.deliver(log).await {
+                return err;
+            }
+        }
+        DwatError::SubscriptionClosed
+    }
+
+    /// Scans for new logs every `config.poll_interval` until a request
+    /// fails, for endpoints without subscriptions. Orphaned blocks are found
+    /// the same way as after a reconnect.
+    async fn poll(&mut self, provider: &Provider<Transport>) -> DwatError {
+        let mut interval = time::interval(self.config.poll_interval);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.catch_up(provider).await {
+                return err;
+            }
+        }
+    }
+
+    /// Undoes blocks in the window that are no longer canonical, then
+    /// fetches the logs emitted since the last scanned block and remembers
+    /// the hashes of the scanned blocks, with or without matching logs.
+    async fn catch_up(&mut self, provider: &Provider<Transport>) -> Result<(), DwatError> {
+        // Blocks link by hash, so a canonical block vouches for everything
+        // below it: only the newest entry is fetched unless it was orphaned,
+        // and then each orphan's parent hash settles the entry below it.
+        let mut orphaned = None;
+        let mut next = self.window.keys().next_back().copied();
+        while let Some(number) = next {
+            let block = provider.get_block(number).await?;
+            if block.as_ref().and_then(|block| block.hash)
+                == self.window.get(&number).map(|e| e.hash)
+            {
+                break;
+            }
+            orphaned = Some(number);
+            next = self
+                .window
+                .range(..number)
+                .next_back()
+                .and_then(|(&below, entry)| {
+                    let vouched = below + 1 == number
+                        && block.as_ref().map(|block| block.parent_hash) == Some(entry.hash);
+                    (!vouched).then_some(below)
+                });
+        }
+        if let Some(lowest) = orphaned {
+            self.rewind(lowest).await?;
+        }
+        // Everything left in the window is canonical now.
+        let verified = self.window.keys().next_back().copied();
+
+        let head = provider.get_block_number().await?.as_u64();
+        let Some(from) = self.synced_to.max(verified) else {
+            self.synced_to = Some(head);
+            return Ok(());
+        };
+
+        let mut start = from;
+        while start <= head {
+            let end = (start + LOG_RANGE - 1).min(head);
+            let filter = self.filter.clone().from_block(start).to_block(end);
+            for log in provider.get_logs(&filter).await? {
+                self.deliver(log).await?;
+            }
+            start = end + 1;
+        }
+
+        let oldest = from.max(head.saturating_sub(self.config.reorg_depth as u64));
+        for number in oldest..=head {
+            if verified.is_some_and(|verified| number <= verified) {
+                continue;
+            }
+            let Some(hash) = provider
+                .get_block(number)
+                .await?
+                .and_then(|block| block.hash)
+            else {
+                continue;
+            };
+            match self.window.get(&number) {
+                // A reorg between the two calls; scanned again next time.
+                Some(entry) if entry.hash != hash => {
+                    self.rewind(number).await?;
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => {
+                    self.window.insert(
+                        number,
+                        BlockLogs {
+                            hash,
+                            logs: Vec::new(),
+                        },
+                    );
+                }
+            }
+        }
+        self.trim();
+        self.synced_to = Some(head);
+        Ok(())
+    }
+
+    /// Removes block `lowest` and every block above it from the window, and
+    /// makes the next scan start below it.
+    async fn rewind(&mut self, lowest: u64) -> Result<(), DwatError> {
+        let numbers: Vec<u64> = self.window.range(lowest..).rev().map(|(&n, _)| n).collect();
+        for number in numbers {
+            self.remove_block(number).await?;
+        }
+        let rescan = lowest.saturating_sub(1);
+        self.synced_to = self.synced_to.map(|synced| synced.min(rescan));
+        Ok(())
+    }
+
+    async fn deliver(&mut self, log: Log) -> Result<(), DwatError> {
+        let (Some(number), Some(hash)) = (log.block_number, log.block_hash) else {
+            return Ok(());
+        };
+        let number = number.as_u64();
+
+        if log.removed == Some(true) {
+            let removed = self.window.get_mut(&number).and_then(|entry| {
+                let index = entry
+                    .logs
+                    .iter()
+                    .position(|l| l.block_hash == log.block_hash && l.log_index == log.log_index)?;
+                Some(entry.logs.remove(index))
+            });
+            return match removed {
+                Some(log) => self.send(LogEvent::Removed(log)).await,
+                None => Ok(()),
+            };
+        }
+
+        if self
+            .window
+            .get(&number)
+            .is_some_and(|entry| entry.hash != hash)
+        {
+            self.remove_block(number).await?;
+        }
+        let entry = self.window.entry(number).or_insert_with(|| BlockLogs {
+            hash,
+            logs: Vec::new(),
+        });
+        if entry.logs.iter().any(|l| l.log_index == log.log_index) {
+            return Ok(());
+        }
+        entry.logs.push(log.clone());
+        self.trim();
+
+        self.send(LogEvent::Added(log)).await
+    }
+
+    /// Forgets blocks deeper than `config.reorg_depth` below the newest.
+    fn trim(&mut self) {
+        let Some(&newest) = self.window.keys().next_back() else {
+            return;
+        };
+        let depth = self.config.reorg_depth as u64;
+        self.window = self.window.split_off(&newest.saturating_sub(depth));
+    }
+
+    /// Reports every delivered log of block `number` as removed, newest first.
+    async fn remove_block(&mut self, number: u64) -> Result<(), DwatError> {
+        let Some(entry) = self.window.remove(&number) else {
+            return Ok(());
+        };
+        for log in entry.logs.into_iter().rev() {
+            self.send(LogEvent::Removed(log)).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, event: LogEvent) -> Result<(), DwatError> {
+        self.tx
+            .send(Ok(event))
+            .await
+            .map_err(|_| DwatError::SubscriptionClosed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::core::types::{U256, U64};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn supervisor() -> (LogSupervisor, mpsc::Receiver<Result<LogEvent, DwatError>>) {
+        let config = StreamConfig::new("ws://127.0.0.1:1");
+        let (tx, rx) = mpsc::channel(16);
+        let supervisor = LogSupervisor {
+            pool: EndpointPool::from_config(&config),
+            config,
+            filter: Filter::new(),
+            tx,
+            active: 0,
+            window: BTreeMap::new(),
+            synced_to: None,
+        };
+        (supervisor, rx)
+    }
+
+    fn log(number: u64, block: u8, index: u64) -> Log {
+        Log {
+            block_number: Some(U64::from(number)),
+            block_hash: Some(H256::repeat_byte(block)),
+            log_index: Some(U256::from(index)),
+            ..Default::default()
+        }
+    }
+
+    fn removed(log: Log) -> Log {
+        Log {
+            removed: Some(true),
+            ..log
+        }
+    }
+
+    /// The events sent so far as `(added, block hash, log index)`.
+    fn events(rx: &mut mpsc::Receiver<Result<LogEvent, DwatError>>) -> Vec<(bool, u8, u64)> {
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            let (added, log) = match event.unwrap() {
+                LogEvent::Added(log) => (true, log),
+                LogEvent::Removed(log) => (false, log),
+            };
+            events.push((
+                added,
+                log.block_hash.unwrap().0[0],
+                log.log_index.unwrap().as_u64(),
+            ));
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn retracts_a_delivered_log_flagged_as_removed() {
+        let (mut supervisor, mut rx) = supervisor();
+        supervisor.deliver(log(10, 0xa, 0)).await.unwrap();
+        supervisor.deliver(log(10, 0xa, 1)).await.unwrap();
+        supervisor.deliver(removed(log(10, 0xa, 0))).await.unwrap();
+
+        assert_eq!(
+            events(&mut rx),
+            [(true, 0xa, 0), (true, 0xa, 1), (false, 0xa, 0)]
+        );
+        assert_eq!(supervisor.window[&10].logs.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ignores_removed_logs_that_were_never_delivered() {
+        let (mut supervisor, mut rx) = supervisor();
+        supervisor.deliver(removed(log(10, 0xa, 0))).await.unwrap();
+        supervisor.deliver(log(11, 0xb, 0)).await.unwrap();
+        supervisor.deliver(removed(log(11, 0xb, 3))).await.unwrap();
+
+        assert_eq!(events(&mut rx), [(true, 0xb, 0)]);
+    }
+
+    #[tokio::test]
+    async fn drops_duplicate_deliveries() {
+        let (mut supervisor, mut rx) = supervisor();
+        supervisor.deliver(log(10, 0xa, 0)).await.unwrap();
+        supervisor.deliver(log(10, 0xa, 0)).await.unwrap();
+
+        assert_eq!(events(&mut rx), [(true, 0xa, 0)]);
+    }
+
+    #[tokio::test]
+    async fn removes_the_logs_of_a_replaced_block() {
+        let (mut supervisor, mut rx) = supervisor();
+        supervisor.deliver(log(10, 0xa, 0)).await.unwrap();
+        supervisor.deliver(log(10, 0xa, 1)).await.unwrap();
+        supervisor.deliver(log(10, 0xb, 0)).await.unwrap();
+
+        assert_eq!(
+            events(&mut rx),
+            [
+                (true, 0xa, 0),
+                (true, 0xa, 1),
+                (false, 0xa, 1),
+                (false, 0xa, 0),
+                (true, 0xb, 0),
+            ]
+        );
+        assert_eq!(supervisor.window[&10].hash, H256::repeat_byte(0xb));
+    }
+}
@@ -1,10 +1,141 @@
-use std::io::Error;
+use std::env;
+use std::str::FromStr;
 
-use dwat::read;
+use dwat::swap::execute::BPS;
+use dwat::swap::{Route, SwapOrder};
+use dwat::{
+    backfill, execute_swap, feed, heads, index, logs, mempool, read, serve, solana, solana_idl,
+    solana_tokens, solana_watch, swaps, token, token_transfers, BackfillConfig, Chain,
+};
+use ethers::core::types::{Address, U256};
 
 #[tokio::main]
-async fn main() -> Result<(), Error>{
-    read().await;
-    Ok(())
+async fn main() -> eyre::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
 
+    match args.first().map(String::as_str) {
+        None => read(false).await,
+        Some("read") => read(has_flag(&args[1..], "--full")).await,
+        Some("logs") => logs().await,
+        Some("mempool") => mempool().await,
+        Some("solana") if has_flag(&args[1..], "--watch") => solana_watch().await,
+        Some("solana") if has_flag(&args[1..], "--tokens") => solana_tokens().await,
+        Some("solana") if has_flag(&args[1..], "--idl") => solana_idl().await,
+        Some("solana") => solana().await,
+        Some("heads") => heads(chain(&args[1..])?).await,
+        Some("index") => index().await,
+        Some("serve") => serve().await,
+        Some("feed") => feed(chain(&args[1..])?).await,
+        Some("swaps") => swaps().await,
+        Some("transfers") => token_transfers().await,
+        Some("token") => {
+            token(
+                token_address(&args[1..])?,
+                has_flag(&args[1..], "--refresh"),
+            )
+            .await
+        }
+        Some("swap") => execute_swap(swap_order(&args[1..])?).await,
+        Some("backfill") => backfill(backfill_config(&args[1..])?).await,
+        Some(other) => eyre::bail!("unknown command: {}", other),
+    }
+}
+
+/// Parses `--from <n> --to <n> [--workers <n>] [--chunk-size <n>]`.
+fn backfill_config(args: &[String]) -> eyre::Result<BackfillConfig> {
+    let mut config = BackfillConfig::new(flag(args, "--from")?, flag(args, "--to")?);
+    if has_flag(args, "--workers") {
+        config.workers = flag(args, "--workers")?;
+    }
+    if has_flag(args, "--chunk-size") {
+        config.chunk_size = flag(args, "--chunk-size")?;
+    }
+    Ok(config)
+}
+
+/// Parses `--router <addr> --amount-in <wei> [--slippage-bps <n>]
+/// [--recipient <addr>]` followed by either `--path <addr>,<addr>,...` for a
+/// V2 router or `--quoter <addr> --token-in <addr> --token-out <addr>
+/// --fee <n>` for a V3 router.
+fn swap_order(args: &[String]) -> eyre::Result<SwapOrder> {
+    let router: Address = flag(args, "--router")?;
+    let route = if has_flag(args, "--path") {
+        let path: String = flag(args, "--path")?;
+        Route::V2 {
+            router,
+            path: path
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| eyre::eyre!("invalid --path: {}", path))?,
+        }
+    } else {
+        Route::V3 {
+            router,
+            quoter: flag(args, "--quoter")?,
+            token_in: flag(args, "--token-in")?,
+            token_out: flag(args, "--token-out")?,
+            fee: flag(args, "--fee")?,
+        }
+    };
+
+    let amount_in: String = flag(args, "--amount-in")?;
+    let amount_in = U256::from_dec_str(&amount_in)
+        .map_err(|_| eyre::eyre!("invalid value for --amount-in: {}", amount_in))?;
+
+    let mut order = SwapOrder::new(route, amount_in);
+    if has_flag(args, "--slippage-bps") {
+        order.slippage_bps = flag(args, "--slippage-bps")?;
+        if order.slippage_bps > BPS {
+            eyre::bail!("--slippage-bps must be at most {}", BPS);
+        }
+    }
+    if has_flag(args, "--recipient") {
+        order.recipient = Some(flag(args, "--recipient")?);
+    }
+    Ok(order)
+}
+
+/// Parses the `<address>` argument of `token`.
+fn token_address(args: &[String]) -> eyre::Result<Address> {
+    let address = args
+        .first()
+        .ok_or_else(|| eyre::eyre!("missing token address"))?;
+    address
+        .parse()
+        .map_err(|_| eyre::eyre!("invalid token address: {}", address))
+}
+
+/// Parses `[--chain evm|solana]`, defaulting to EVM.
+fn chain(args: &[String]) -> eyre::Result<Chain> {
+    if has_flag(args, "--chain") {
+        flag(args, "--chain")
+    } else {
+        Ok(Chain::Evm)
+    }
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+fn flag<T: FromStr>(args: &[String], name: &str) -> eyre::Result<T> {
+    let value = args
+        .iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .ok_or_else(|| eyre::eyre!("missing {}", name))?;
+
+    // Allow digit separators in numbers such as block heights.
+    let numeric = value
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == ',' || c == '_');
+    let value = if numeric {
+        value.replace([',', '_'], "")
+    } else {
+        value.clone()
+    };
+    value
+        .parse()
+        .map_err(|_| eyre::eyre!("invalid value for {}: {}", name, value))
 }
@@ -0,0 +1,590 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use ethers::{
+    core::types::{Address, Block, Bytes, Transaction, H256, U256, U64},
+    providers::{Middleware, Provider},
+};
+use futures::{stream, FutureExt, Stream, StreamExt};
+use serde::Serialize;
+
+use crate::config::{parse_list_var, parse_var, StreamConfig};
+use crate::endpoints::EndpointPool;
+use crate::error::DwatError;
+use crate::reconnect::{Outbox, Supervised};
+use crate::transport::{self, Transport};
+
+/// Pending hashes hydrated concurrently unless `MEMPOOL_CONCURRENCY` says
+/// otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 32;
+
+/// How long a transaction may stay pending before the node is asked whether
+/// it still knows it, unless `MEMPOOL_DROP_AFTER` says otherwise.
+pub const DEFAULT_DROP_AFTER: Duration = Duration::from_secs(600);
+
+/// Transactions tracked at once; further matches are ignored until some
+/// leave the mempool.
+pub const DEFAULT_MAX_TRACKED: usize = 10_000;
+
+/// Selects which pending transactions are tracked. Empty lists match
+/// everything.
+#[derive(Debug, Clone, Default)]
+pub struct MempoolFilter {
+    pub to: Vec<Address>,
+    pub from: Vec<Address>,
+    /// Leading bytes of the calldata, usually a 4-byte method selector.
+    pub selectors: Vec<Bytes>,
+}
+
+impl MempoolFilter {
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        (self.to.is_empty() || tx.to.is_some_and(|to| self.to.contains(&to)))
+            && (self.from.is_empty() || self.from.contains(&tx.from))
+            && (self.selectors.is_empty()
+                || self
+                    .selectors
+                    .iter()
+                    .any(|selector| tx.input.starts_with(selector)))
+    }
+}
+
+/// Settings for a [`MempoolStream`].
+#[derive(Debug, Clone)]
+pub struct MempoolConfig {
+    pub stream: StreamConfig,
+    pub filter: MempoolFilter,
+    /// Pending hashes hydrated to full transactions concurrently.
+    pub concurrency: usize,
+    pub drop_after: Duration,
+    pub max_tracked: usize,
+}
+
+impl MempoolConfig {
+    pub fn new(stream: StreamConfig) -> Self {
+        Self {
+            stream,
+            filter: MempoolFilter::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            drop_after: DEFAULT_DROP_AFTER,
+            max_tracked: DEFAULT_MAX_TRACKED,
+        }
+    }
+
+    /// Reads the stream settings plus the optional comma-separated
+    /// `MEMPOOL_TO`, `MEMPOOL_FROM` and `MEMPOOL_SELECTOR`, and
+    /// `MEMPOOL_CONCURRENCY` and `MEMPOOL_DROP_AFTER` (seconds).
+    pub fn from_env() -> Result<Self, DwatError> {
+        let mut config = Self::new(StreamConfig::from_env()?);
+        if let Some(to) = parse_list_var("MEMPOOL_TO")? {
+            config.filter.to = to;
+        }
+        if let Some(from) = parse_list_var("MEMPOOL_FROM")? {
+            config.filter.from = from;
+        }
+        if let Some(selectors) = parse_list_var("MEMPOOL_SELECTOR")? {
+            config.filter.selectors = selectors;
+        }
+        if let Some(concurrency) = parse_var("MEMPOOL_CONCURRENCY")? {
+            config.concurrency = concurrency;
+        }
+        if let Some(secs) = parse_var("MEMPOOL_DROP_AFTER")? {
+            config.drop_after = Duration::from_secs(secs);
+        }
+        Ok(config)
+    }
+}
+
+/// A step in the life of a tracked pending transaction. `pending_ms` is the
+/// time since it was first seen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MempoolEvent {
+    /// A matching transaction entered the mempool.
+    Pending { transaction: Box<Transaction> },
+    /// The transaction was included in a block.
+    Mined {
+        hash: H256,
+        block_number: u64,
+        block_hash: H256,
+        pending_ms: u64,
+    },
+    /// Another transaction with the same sender and nonce took its place.
+    Replaced {
+        hash: H256,
+        by: H256,
+        pending_ms: u64,
+    },
+    /// The node no longer knows the transaction.
+    Dropped { hash: H256, pending_ms: u64 },
+}
+
+/// A stream of lifecycle events for pending transactions.
+///
+/// `newPendingTransactions` and `newHeads` are followed over one WebSocket
+/// connection. Announced hashes are hydrated with bounded concurrency and
+/// those matching the filter are tracked until they are mined, replaced by
+/// another transaction with the same nonce, or dropped by the node. The
+/// connection is re-established with backoff and blocks mined during an
+/// outage are still checked, up to `reorg_depth` of them.
+pub struct MempoolStream {
+    inner: Supervised<MempoolEvent>,
+}
+
+impl MempoolStream {
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(MempoolConfig::from_env()?).await
+    }
+
+    pub async fn connect_with(config: MempoolConfig) -> Result<Self, DwatError> {
+        if let Some(url) = config.stream.endpoints().find(|url| transport::polls(url)) {
+            return Err(DwatError::Config(format!(
+                "mempool monitoring needs a WebSocket or IPC endpoint, not {}",
+                url
+            )));
+        }
+
+        let mut pool = EndpointPool::from_config(&config.stream);
+        let (active, provider) = pool.connect().await?;
+
+        let inner = Supervised::spawn(
+            config.stream.reconnect.clone(),
+            |tx| Monitor {
+                config,
+                tx,
+                pool,
+                active,
+                tracked: HashMap::new(),
+                senders: HashMap::new(),
+                head: None,
+            },
+            provider,
+            |monitor| monitor.reconnect().boxed(),
+            |monitor, provider| {
+                async move {
+                    let err = monitor.follow(provider).await;
+                    monitor.pool.failed(monitor.active);
+                    err
+                }
+                .boxed()
+            },
+        );
+
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for MempoolStream {
+    type Item = Result<MempoolEvent, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+struct Tracked {
+    from: Address,
+    nonce: U256,
+    seen: Instant,
+    /// Last time the node was asked about it, or when it was first seen.
+    checked: Instant,
+}
+
+impl Tracked {
+    fn pending_ms(&self) -> u64 {
+        self.seen.elapsed().as_millis() as u64
+    }
+}
+
+/// Tracks pending transactions for a [`MempoolStream`].
+struct Monitor {
+    config: MempoolConfig,
+    tx: Outbox<MempoolEvent>,
+    pool: EndpointPool,
+    /// Index of the endpoint currently followed.
+    active: usize,
+    tracked: HashMap<H256, Tracked>,
+    /// Tracked hash by sender and nonce, to spot replacements.
+    senders: HashMap<(Address, U256), H256>,
+    /// Highest block checked for inclusions.
+    head: Option<u64>,
+}
+
+impl Monitor {
+    /// Connects to the healthiest endpoint.
+    async fn reconnect(&mut self) -> Result<Provider<Transport>, DwatError> {
+        let (active, provider) = self.pool.connect().await?;
+        self.active = active;
+        Ok(provider)
+    }
+
+    /// Follows pending transactions and heads until either subscription
+    /// fails, returning the cause.
+    async fn follow(&mut self, provider: &Provider<Transport>) -> DwatError {
+        let pending = match provider.subscribe_pending_txs().await {
+            Ok(sub) => sub,
+            Err(err) => return err.into(),
+        };
+        let mut heads = match provider.subscribe_blocks().await {
+            Ok(sub) => sub,
+            Err(err) => return err.into(),
+        };
+
+        match provider.get_block_number().await {
+            Ok(head) => {
+                if let Err(err) = self.on_head(provider, Some(head)).await {
+                    return err;
+                }
+            }
+            Err(err) => return err.into(),
+        }
+
+        let mut hydrated = pending
+            .map(|hash| async move { (hash, provider.get_transaction(hash).await) })
+            .buffer_unordered(self.config.concurrency);
+
+        loop {
+            let result = tokio::select! {
+                tx = hydrated.next() => match tx {
+                    Some((_, Ok(Some(tx)))) => self.observe(tx).await,
+                    // Already gone from the node's pool.
+                    Some((_, Ok(None))) => Ok(()),
+                    // One lookup failing says little about the connection,
+                    // which the heads subscription keeps watching.
+                    Some((hash, Err(err))) => {
+                        log::warn!("skipping pending transaction {:?}: {}", hash, err);
+                        Ok(())
+                    }
+                    None => Err(DwatError::SubscriptionClosed),
+                },
+                block = heads.next() => match block {
+                    Some(block) => self.on_head(provider, block.number).await,
+                    None => Err(DwatError::SubscriptionClosed),
+                },
+            };
+            if let Err(err) = result {
+                return err;
+            }
+        }
+    }
+
+    async fn observe(&mut self, tx: Transaction) -> Result<(), DwatError> {
+        if tx.block_number.is_some() {
+            return Ok(());
+        }
+
+        let key = (tx.from, tx.nonce);
+        if let Some(&old) = self.senders.get(&key) {
+            if old == tx.hash {
+                return Ok(());
+            }
+            if let Some(tracked) = self.untrack(old) {
+                let pending_ms = tracked.pending_ms();
+                self.send(MempoolEvent::Replaced {
+                    hash: old,
+                    by: tx.hash,
+                    pending_ms,
+                })
+                .await?;
+            }
+        }
+
+        if !self.config.filter.matches(&tx) || self.tracked.len() >= self.config.max_tracked {
+            return Ok(());
+        }
+        let now = Instant::now();
+        self.tracked.insert(
+            tx.hash,
+            Tracked {
+                from: tx.from,
+                nonce: tx.nonce,
+                seen: now,
+                checked: now,
+            },
+        );
+        self.senders.insert(key, tx.hash);
+        self.send(MempoolEvent::Pending {
+            transaction: Box::new(tx),
+        })
+        .await
+    }
+
+    /// Checks every block since the last head for tracked senders, then asks
+    /// the node about transactions that have been pending too long.
+    async fn on_head(
+        &mut self,
+        provider: &Provider<Transport>,
+        number: Option<U64>,
+    ) -> Result<(), DwatError> {
+        let Some(number) = number.map(|number| number.as_u64()) else {
+            return Ok(());
+        };
+        let oldest = number.saturating_sub(self.config.stream.reorg_depth as u64);
+        let from = match self.head {
+            Some(head) if head < number => (head + 1).max(oldest),
+            _ => number,
+        };
+
+        for n in from..=number {
+            let block = provider
+                .get_block_with_txs(n)
+                .await?
+                .ok_or(DwatError::MissingBlock(n))?;
+            self.included(&block).await?;
+        }
+        self.head = Some(self.head.map_or(number, |head| head.max(number)));
+
+        self.expire(provider).await
+    }
+
+    async fn included(&mut self, block: &Block<Transaction>) -> Result<(), DwatError> {
+        let (Some(number), Some(block_hash)) = (block.number, block.hash) else {
+            return Ok(());
+        };
+        for tx in &block.transactions {
+            let Some(&hash) = self.senders.get(&(tx.from, tx.nonce)) else {
+                continue;
+            };
+            let Some(tracked) = self.untrack(hash) else {
+                continue;
+            };
+            let pending_ms = tracked.pending_ms();
+            let event = if hash == tx.hash {
+                MempoolEvent::Mined {
+                    hash,
+                    block_number: number.as_u64(),
+                    block_hash,
+                    pending_ms,
+                }
+            } else {
+                MempoolEvent::Replaced {
+                    hash,
+                    by: tx.hash,
+                    pending_ms,
+                }
+            };
+            self.send(event).await?;
+        }
+        Ok(())
+    }
+
+    async fn expire(&mut self, provider: &Provider<Transport>) -> Result<(), DwatError> {
+        let due: Vec<H256> = self
+            .tracked
+            .iter()
+            .filter(|(_, tracked)| tracked.checked.elapsed() >= self.config.drop_after)
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        let answers: Vec<_> = stream::iter(due)
+            .map(|hash| async move { (hash, provider.get_transaction(hash).await) })
+            .buffer_unordered(self.config.concurrency)
+            .collect()
+            .await;
+
+        for (hash, answer) in answers {
+            match answer? {
+                None => {
+                    if let Some(tracked) = self.untrack(hash) {
+                        let pending_ms = tracked.pending_ms();
+                        self.send(MempoolEvent::Dropped { hash, pending_ms })
+                            .await?;
+                    }
+                }
+                Some(Transaction {
+                    block_number: Some(number),
+                    block_hash: Some(block_hash),
+                    ..
+                }) => {
+                    if let Some(tracked) = self.untrack(hash) {
+                        let pending_ms = tracked.pending_ms();
+                        self.send(MempoolEvent::Mined {
+                            hash,
+                            block_number: number.as_u64(),
+                            block_hash,
+                            pending_ms,
+                        })
+                        .await?;
+                    }
+                }
+                Some(_) => {
+                    if let Some(tracked) = self.tracked.get_mut(&hash) {
+                        tracked.checked = Instant::now();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn untrack(&mut self, hash: H256) -> Option<Tracked> {
+        let tracked = self.tracked.remove(&hash)?;
+        self.senders.remove(&(tracked.from, tracked.nonce));
+        Some(tracked)
+    }
+
+    async fn send(&self, event: MempoolEvent) -> Result<(), DwatError> {
+        self.tx
+            .send(Ok(event))
+            .await
+            .map_err(|_| DwatError::SubscriptionClosed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn tx(from: u8, nonce: u64, hash: u64) -> Transaction {
+        Transaction {
+            hash: H256::from_low_u64_be(hash),
+            from: Address::repeat_byte(from),
+            nonce: U256::from(nonce),
+            to: Some(Address::repeat_byte(0xee)),
+            input: Bytes::from(vec![0xa9, 0x05, 0x9c, 0xbb, 1, 2]),
+            ..Default::default()
+        }
+    }
+
+    fn monitor() -> (Monitor, mpsc::Receiver<Result<MempoolEvent, DwatError>>) {
+        let config = MempoolConfig::new(StreamConfig::new("ws://127.0.0.1:1"));
+        let (tx, rx) = mpsc::channel(16);
+        let monitor = Monitor {
+            pool: EndpointPool::from_config(&config.stream),
+            config,
+            tx,
+            active: 0,
+            tracked: HashMap::new(),
+            senders: HashMap::new(),
+            head: None,
+        };
+        (monitor, rx)
+    }
+
+    fn block(number: u64, transactions: Vec<Transaction>) -> Block<Transaction> {
+        Block {
+            number: Some(U64::from(number)),
+            hash: Some(H256::repeat_byte(number as u8)),
+            transactions,
+            ..Default::default()
+        }
+    }
+
+    /// Event kinds and the hashes they name, drained from `rx`.
+    fn events(rx: &mut mpsc::Receiver<Result<MempoolEvent, DwatError>>) -> Vec<(String, u64)> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| match event.unwrap() {
+                MempoolEvent::Pending { transaction } => {
+                    ("pending".into(), transaction.hash.to_low_u64_be())
+                }
+                MempoolEvent::Mined { hash, .. } => ("mined".into(), hash.to_low_u64_be()),
+                MempoolEvent::Replaced { hash, by, .. } => (
+                    format!("replaced by {}", by.to_low_u64_be()),
+                    hash.to_low_u64_be(),
+                ),
+                MempoolEvent::Dropped { hash, .. } => ("dropped".into(), hash.to_low_u64_be()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = MempoolFilter::default();
+        assert!(filter.matches(&tx(1, 0, 1)));
+        assert!(filter.matches(&Transaction {
+            to: None,
+            ..tx(1, 0, 1)
+        }));
+    }
+
+    #[test]
+    fn filters_by_recipient_sender_and_selector() {
+        let filter = MempoolFilter {
+            to: vec![Address::repeat_byte(0xee)],
+            ..Default::default()
+        };
+        assert!(filter.matches(&tx(1, 0, 1)));
+        // A contract creation has no recipient to match.
+        assert!(!filter.matches(&Transaction {
+            to: None,
+            ..tx(1, 0, 1)
+        }));
+
+        let filter = MempoolFilter {
+            from: vec![Address::repeat_byte(2)],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&tx(1, 0, 1)));
+        assert!(filter.matches(&tx(2, 0, 1)));
+
+        let filter = MempoolFilter {
+            selectors: vec![Bytes::from(vec![0xa9, 0x05, 0x9c, 0xbb])],
+            ..Default::default()
+        };
+        assert!(filter.matches(&tx(1, 0, 1)));
+        let mut other = tx(1, 0, 1);
+        other.input = Bytes::from(vec![0x09, 0x5e, 0xa7, 0xb3]);
+        assert!(!filter.matches(&other));
+        other.input = Bytes::from(vec![0xa9, 0x05]);
+        assert!(!filter.matches(&other));
+    }
+
+    #[tokio::test]
+    async fn reports_a_pending_replacement_with_the_same_nonce() {
+        let (mut monitor, mut rx) = monitor();
+        monitor.observe(tx(1, 5, 10)).await.unwrap();
+        // Seen again, e.g. after a reconnect.
+        monitor.observe(tx(1, 5, 10)).await.unwrap();
+        monitor.observe(tx(1, 5, 11)).await.unwrap();
+
+        assert_eq!(
+            events(&mut rx),
+            [
+                ("pending".into(), 10),
+                ("replaced by 11".into(), 10),
+                ("pending".into(), 11)
+            ]
+        );
+        assert_eq!(monitor.tracked.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_mined_and_replaced_in_a_block() {
+        let (mut monitor, mut rx) = monitor();
+        monitor.observe(tx(1, 0, 10)).await.unwrap();
+        monitor.observe(tx(2, 0, 20)).await.unwrap();
+        events(&mut rx);
+
+        // Sender 2's nonce was mined under a hash never seen pending.
+        let mut replacement = tx(2, 0, 21);
+        replacement.block_number = Some(U64::from(7));
+        monitor
+            .included(&block(7, vec![tx(1, 0, 10), replacement, tx(3, 0, 30)]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events(&mut rx),
+            [("mined".into(), 10), ("replaced by 21".into(), 20)]
+        );
+        assert!(monitor.tracked.is_empty());
+        assert!(monitor.senders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ignores_mined_and_unmatched_transactions() {
+        let (mut monitor, mut rx) = monitor();
+        monitor.config.filter.from = vec![Address::repeat_byte(1)];
+
+        let mut mined = tx(1, 0, 10);
+        mined.block_number = Some(U64::one());
+        monitor.observe(mined).await.unwrap();
+        monitor.observe(tx(2, 0, 20)).await.unwrap();
+
+        assert!(events(&mut rx).is_empty());
+        assert!(monitor.tracked.is_empty());
+    }
+}
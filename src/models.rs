@@ -0,0 +1,232 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use ethers::core::types::{Block, Log, Transaction, TransactionReceipt, U256};
+use serde::Serialize;
+
+use crate::schema::{
+    blocks, logs, receipts, token_balance_changes, token_balances, token_transfers, tokens,
+    transactions,
+};
+use crate::tokens::TokenMetadata;
+use crate::transfers::TokenTransfer;
+
+/// Lower-case `0x`-prefixed hex, as stored in the text columns.
+pub fn hex<T: std::fmt::Debug>(value: T) -> String {
+    format!("{:?}", value)
+}
+
+/// A `U256` as an exact `NUMERIC`.
+pub fn numeric(value: U256) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).expect("U256 is a valid decimal")
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = blocks)]
+pub struct BlockRow {
+    pub hash: String,
+    pub number: i64,
+    pub parent_hash: String,
+    pub timestamp: i64,
+    pub miner: Option<String>,
+    pub gas_used: i64,
+    pub gas_limit: i64,
+    pub base_fee_per_gas: Option<BigDecimal>,
+    pub transaction_count: i32,
+}
+
+impl<T> From<&Block<T>> for BlockRow {
+    fn from(block: &Block<T>) -> Self {
+        Self {
+            hash: hex(block.hash.unwrap_or_default()),
+            number: block.number.unwrap_or_default().as_u64() as i64,
+            parent_hash: hex(block.parent_hash),
+            timestamp: block.timestamp.low_u64() as i64,
+            miner: block.author.map(hex),
+            gas_used: block.gas_used.low_u64() as i64,
+            gas_limit: block.gas_limit.low_u64() as i64,
+            base_fee_per_gas: block.base_fee_per_gas.map(numeric),
+            transaction_count: block.transactions.len() as i32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = transactions)]
+pub struct TransactionRow {
+    pub hash: String,
+    pub block_hash: String,
+    pub block_number: i64,
+    pub transaction_index: i32,
+    pub from_address: String,
+    pub to_address: Option<String>,
+    pub value: BigDecimal,
+    pub gas: BigDecimal,
+    pub gas_price: Option<BigDecimal>,
+    pub nonce: BigDecimal,
+    pub input: String,
+}
+
+impl From<&Transaction> for TransactionRow {
+    fn from(tx: &Transaction) -> Self {
+        Self {
+            hash: hex(tx.hash),
+            block_hash: hex(tx.block_hash.unwrap_or_default()),
+            block_number: tx.block_number.unwrap_or_default().as_u64() as i64,
+            transaction_index: tx.transaction_index.unwrap_or_default().as_u64() as i32,
+            from_address: hex(tx.from),
+            to_address: tx.to.map(hex),
+            value: numeric(tx.value),
+            gas: numeric(tx.gas),
+            gas_price: tx.gas_price.map(numeric),
+            nonce: numeric(tx.nonce),
+            input: tx.input.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = receipts)]
+pub struct ReceiptRow {
+    pub transaction_hash: String,
+    pub block_hash: String,
+    pub block_number: i64,
+    pub status: Option<i16>,
+    pub gas_used: Option<BigDecimal>,
+    pub cumulative_gas_used: BigDecimal,
+    pub effective_gas_price: Option<BigDecimal>,
+    pub contract_address: Option<String>,
+}
+
+impl From<&TransactionReceipt> for ReceiptRow {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        Self {
+            transaction_hash: hex(receipt.transaction_hash),
+            block_hash: hex(receipt.block_hash.unwrap_or_default()),
+            block_number: receipt.block_number.unwrap_or_default().as_u64() as i64,
+            status: receipt.status.map(|s| s.as_u64() as i16),
+            gas_used: receipt.gas_used.map(numeric),
+            cumulative_gas_used: numeric(receipt.cumulative_gas_used),
+            effective_gas_price: receipt.effective_gas_price.map(numeric),
+            contract_address: receipt.contract_address.map(hex),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = logs)]
+pub struct LogRow {
+    pub block_hash: String,
+    pub log_index: i32,
+    pub block_number: i64,
+    pub transaction_hash: String,
+    pub address: String,
+    pub topic0: Option<String>,
+    pub topic1: Option<String>,
+    pub topic2: Option<String>,
+    pub topic3: Option<String>,
+    pub data: String,
+}
+
+impl From<&Log> for LogRow {
+    fn from(log: &Log) -> Self {
+        let topic = |i: usize| log.topics.get(i).map(hex);
+        Self {
+            block_hash: hex(log.block_hash.unwrap_or_default()),
+            log_index: log.log_index.unwrap_or_default().low_u32() as i32,
+            block_number: log.block_number.unwrap_or_default().as_u64() as i64,
+            transaction_hash: hex(log.transaction_hash.unwrap_or_default()),
+            address: hex(log.address),
+            topic0: topic(0),
+            topic1: topic(1),
+            topic2: topic(2),
+            topic3: topic(3),
+            data: log.data.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = token_transfers)]
+pub struct TokenTransferRow {
+    pub block_hash: String,
+    pub log_index: i32,
+    pub batch_index: i32,
+    pub block_number: i64,
+    pub transaction_hash: String,
+    pub token: String,
+    pub standard: String,
+    pub operator: Option<String>,
+    pub from_address: String,
+    pub to_address: String,
+    pub token_id: Option<BigDecimal>,
+    pub value: BigDecimal,
+}
+
+impl From<&TokenTransfer> for TokenTransferRow {
+    fn from(transfer: &TokenTransfer) -> Self {
+        Self {
+            block_hash: hex(transfer.block_hash),
+            log_index: transfer.log_index as i32,
+            batch_index: transfer.batch_index as i32,
+            block_number: transfer.block_number as i64,
+            transaction_hash: hex(transfer.transaction_hash),
+            token: hex(transfer.token),
+            standard: transfer.standard.as_str().to_string(),
+            operator: transfer.operator.map(hex),
+            from_address: hex(transfer.from),
+            to_address: hex(transfer.to),
+            token_id: transfer.token_id.map(numeric),
+            value: numeric(transfer.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = token_balance_changes)]
+pub struct BalanceChangeRow {
+    pub block_hash: String,
+    pub block_number: i64,
+    pub holder: String,
+    pub token: String,
+    pub token_id: BigDecimal,
+    pub delta: BigDecimal,
+}
+
+/// A holder's balance of one token, and the block it last changed in.
+///
+/// The balance is the net of the transfers indexed so far. When indexing
+/// started after the token was deployed it misses earlier transfers and can
+/// be below the real balance, or negative.
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = token_balances)]
+pub struct TokenBalanceRow {
+    pub holder: String,
+    pub token: String,
+    pub token_id: BigDecimal,
+    pub balance: BigDecimal,
+    pub block_number: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name = tokens)]
+pub struct TokenRow {
+    pub address: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<i16>,
+    pub total_supply: Option<BigDecimal>,
+}
+
+impl From<&TokenMetadata> for TokenRow {
+    fn from(token: &TokenMetadata) -> Self {
+        Self {
+            address: hex(token.address),
+            name: token.name.clone(),
+            symbol: token.symbol.clone(),
+            decimals: token.decimals.map(i16::from),
+            total_supply: token.total_supply.map(numeric),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::error::DwatError;
+use crate::models::{BlockRow, LogRow, ReceiptRow, TokenBalanceRow, TransactionRow};
+use crate::schema::{blocks, logs, receipts, token_balance_changes, token_balances, transactions};
+
+/// Largest page any query returns.
+pub const MAX_LIMIT: i64 = 500;
+
+/// One page of results and the cursor for the next, if there is more.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Position of the last item of a page: a block number and the index of
+/// the transaction or log within that block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub block_number: i64,
+    pub index: i32,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.block_number, self.index)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = DwatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DwatError::Config(format!("invalid cursor: {}", s));
+        let (number, index) = s.split_once('-').ok_or_else(invalid)?;
+        let cursor = Cursor {
+            block_number: number.parse().map_err(|_| invalid())?,
+            index: index.parse().map_err(|_| invalid())?,
+        };
+        if cursor.block_number < 0 || cursor.index < 0 {
+            return Err(invalid());
+        }
+        Ok(cursor)
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A block by number or hash.
+#[derive(Debug, Clone)]
+pub enum BlockRef {
+    Number(i64),
+    Hash(String),
+}
+
+impl FromStr for BlockRef {
+    type Err = DwatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            return Ok(BlockRef::Hash(s.to_lowercase()));
+        }
+        s.parse()
+            .map(BlockRef::Number)
+            .map_err(|_| DwatError::Config(format!("invalid block number or hash: {}", s)))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockView {
+    #[serde(flatten)]
+    pub block: BlockRow,
+    pub transactions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionView {
+    #[serde(flatten)]
+    pub transaction: TransactionRow,
+    pub receipt: Option<ReceiptRow>,
+    pub logs: Vec<LogRow>,
+}
+
+/// Filters for [`logs`]; block bounds are inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub address: Option<String>,
+    pub topic0: Option<String>,
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+}
+
+pub fn block(conn: &mut PgConnection, id: &BlockRef) -> Result<Option<BlockView>, DwatError> {
+    let query = blocks::table.select(BlockRow::as_select()).into_boxed();
+    let query = match id {
+        BlockRef::Number(number) => query.filter(blocks::number.eq(*number)),
+        BlockRef::Hash(hash) => query.filter(blocks::hash.eq(hash)),
+    };
+
+    let Some(block) = query.first(conn).optional()? else {
+        return Ok(None);
+    };
+    let transactions = transactions::table
+        .filter(transactions::block_hash.eq(&block.hash))
+        .order(transactions::transaction_index)
+        .select(transactions::hash)
+        .load(conn)?;
+
+    Ok(Some(BlockView {
+        block,
+        transactions,
+    }))
+}
+
+pub fn transaction(
+    conn: &mut PgConnection,
+    hash: &str,
+) -> Result<Option<TransactionView>, DwatError> {
+    let hash = hash.to_lowercase();
+    let Some(transaction) = transactions::table
+        .find(&hash)
+        .select(TransactionRow::as_select())
+        .first(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let receipt = receipts::table
+        .find(&hash)
+        .select(ReceiptRow::as_select())
+        .first(conn)
+        .optional()?;
+    let logs = logs::table
+        .filter(logs::transaction_hash.eq(&hash))
+        .filter(logs::block_hash.eq(&transaction.block_hash))
+        .order(logs::log_index)
+        .select(LogRow::as_select())
+        .load(conn)?;
+
+    Ok(Some(TransactionView {
+        transaction,
+        receipt,
+        logs,
+    }))
+}
+
+/// Transactions sent from or to `address`, newest first.
+pub fn address_transactions(
+    conn: &mut PgConnection,
+    address: &str,
+    cursor: Option<Cursor>,
+    limit: i64,
+) -> Result<Page<TransactionRow>, DwatError> {
+    let address = address.to_lowercase();
+    let mut query = transactions::table
+        .filter(
+            transactions::from_address
+                .eq(&address)
+                .or(transactions::to_address.eq(&address)),
+        )
+        .select(TransactionRow::as_select())
+        .into_boxed();
+
+    if let Some(cursor) = cursor {
+        query = query.filter(
+            transactions::block_number
+                .lt(cursor.block_number)
+                .or(transactions::block_number
+                    .eq(cursor.block_number)
+                    .and(transactions::transaction_index.lt(cursor.index))),
+        );
+    }
+
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let items: Vec<TransactionRow> = query
+        .order((
+            transactions::block_number.desc(),
+            transactions::transaction_index.desc(),
+        ))
+        .limit(limit)
+        .load(conn)?;
+
+    let next_cursor = next(&items, limit, |tx| Cursor {
+        block_number: tx.block_number,
+        index: tx.transaction_index,
+    });
+    Ok(Page { items, next_cursor })
+}
+
+/// Logs matching `filter`, oldest first.
+pub fn logs(
+    conn: &mut PgConnection,
+    filter: &LogFilter,
+    cursor: Option<Cursor>,
+    limit: i64,
+) -> Result<Page<LogRow>, DwatError> {
+    let mut query = logs::table.select(LogRow::as_select()).into_boxed();
+
+    if let Some(address) = &filter.address {
+        query = query.filter(logs::address.eq(address.to_lowercase()));
+    }
+    if let Some(topic0) = &filter.topic0 {
+        query = query.filter(logs::topic0.eq(topic0.to_lowercase()));
+    }
+    if let Some(from) = filter.from_block {
+        query = query.filter(logs::block_number.ge(from));
+    }
+    if let Some(to) = filter.to_block {
+        query = query.filter(logs::block_number.le(to));
+    }
+    if let Some(cursor) = cursor {
+        query = query.filter(
+            logs::block_number
+                .gt(cursor.block_number)
+                .or(logs::block_number
+                    .eq(cursor.block_number)
+                    .and(logs::log_index.gt(cursor.index))),
+        );
+    }
+
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let items: Vec<LogRow> = query
+        .order((logs::block_number, logs::log_index))
+        .limit(limit)
+        .load(conn)?;
+
+    let next_cursor = next(&items, limit, |log| Cursor {
+        block_number: log.block_number,
+        index: log.log_index,
+    });
+    Ok(Page { items, next_cursor })
+}
+
+/// Non-zero token balances of `holder`, optionally of one `token` only.
+///
+/// Without `block` these are the running balances as of the latest indexed
+/// block; with it, the balances at the end of that block. `block_number` is
+/// the last block up to then in which each balance changed.
+///
+/// Balances only count indexed transfers and can be negative; see
+/// [`TokenBalanceRow`].
+pub fn balances(
+    conn: &mut PgConnection,
+    holder: &str,
+    token: Option<&str>,
+    block: Option<i64>,
+) -> Result<Vec<TokenBalanceRow>, DwatError> {
+    let holder = holder.to_lowercase();
+    let token = token.map(str::to_lowercase);
+
+    let Some(block) = block else {
+        let mut query = token_balances::table
+            .filter(token_balances::holder.eq(&holder))
+            .select(TokenBalanceRow::as_select())
+            .into_boxed();
+        if let Some(token) = &token {
+            query = query.filter(token_balances::token.eq(token));
+        }
+        let rows: Vec<TokenBalanceRow> = query
+            .order((token_balances::token, token_balances::token_id))
+            .load(conn)?;
+        return Ok(rows
+            .into_iter()
+            .filter(|row| !row.balance.is_zero())
+            .collect());
+    };
+
+    let mut query = token_balance_changes::table
+        .filter(token_balance_changes::holder.eq(&holder))
+        .filter(token_balance_changes::block_number.le(block))
+        .group_by((
+            token_balance_changes::token,
+            token_balance_changes::token_id,
+        ))
+        .select((
+            token_balance_changes::token,
+            token_balance_changes::token_id,
+            diesel::dsl::sum(token_balance_changes::delta),
+            diesel::dsl::max(token_balance_changes::block_number),
+        ))
+        .into_boxed();
+    if let Some(token) = &token {
+        query = query.filter(token_balance_changes::token.eq(token));
+    }
+    let sums: Vec<(String, BigDecimal, Option<BigDecimal>, Option<i64>)> = query
+        .order((
+            token_balance_changes::token,
+            token_balance_changes::token_id,
+        ))
+        .load(conn)?;
+
+    Ok(sums
+        .into_iter()
+        .filter_map(|(token, token_id, balance, last)| {
+            Some(TokenBalanceRow {
+                holder: holder.clone(),
+                token,
+                token_id,
+                balance: balance.filter(|balance| !balance.is_zero())?,
+                block_number: last?,
+            })
+        })
+        .collect())
+}
+
+/// A full page may be followed by more; a short one is the last.
+fn next<T>(items: &[T], limit: i64, cursor: impl Fn(&T) -> Cursor) -> Option<Cursor> {
+    if (items.len() as i64) < limit {
+        return None;
+    }
+    items.last().map(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_what_it_displays() {
+        let cursor = Cursor {
+            block_number: 18_000_000,
+            index: 42,
+        };
+        assert_eq!(cursor.to_string(), "18000000-42");
+        assert_eq!("18000000-42".parse::<Cursor>().unwrap(), cursor);
+        assert_eq!(
+            serde_json::to_value(cursor).unwrap(),
+            serde_json::json!("18000000-42")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_cursors() {
+        for invalid in [
+            "", "12", "12-", "-3", "a-1", "1-b", "1-2-3", "5--3", "1.5-2",
+        ] {
+            assert!(invalid.parse::<Cursor>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn only_full_pages_have_a_next_cursor() {
+        let cursor = |&(block_number, index): &(i64, i32)| Cursor {
+            block_number,
+            index,
+        };
+        let items = [(1, 0), (1, 1), (2, 0)];
+        assert_eq!(
+            next(&items, 3, cursor),
+            Some(Cursor {
+                block_number: 2,
+                index: 0
+            })
+        );
+        assert_eq!(next(&items, 4, cursor), None);
+        assert_eq!(next::<(i64, i32)>(&[], 0, cursor), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::Stream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::error::DwatError;
+
+/// How a dropped subscription is re-established.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt.
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each failed attempt.
+    pub multiplier: u32,
+    /// Give up after this many consecutive failures; `None` retries forever.
+    pub max_attempts: Option<u32>,
+    /// Treat the connection as dead if no head arrives within this window.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2,
+            max_attempts: None,
+            idle_timeout: Some(Duration::from_secs(120)),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            policy: self.clone(),
+            attempt: 0,
+            delay: self.initial_delay,
+        }
+    }
+}
+
+/// Exponential backoff state for one outage.
+#[derive(Debug)]
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    attempt: u32,
+    delay: Duration,
+}
+
+impl Backoff {
+    /// Returns the delay before the next attempt, or `None` once the
+    /// policy's attempt budget is spent.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if let Some(max) = self.policy.max_attempts {
+            if self.attempt >= max {
+                return None;
+            }
+        }
+        self.attempt += 1;
+
+        let delay = self.delay;
+        self.delay = (self.delay * self.policy.multiplier).min(self.policy.max_delay);
+        Some(delay)
+    }
+}
+
+/// Items buffered between a supervised task and its consumer.
+const BUFFER: usize = 256;
+
+/// Where a supervised task sends its output.
+pub(crate) type Outbox<T> = mpsc::Sender<Result<T, DwatError>>;
+
+/// The output of a background task that keeps a connection alive across
+/// disconnects. The task is aborted when this is dropped.
+pub(crate) struct Supervised<T> {
+    rx: mpsc::Receiver<Result<T, DwatError>>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Send + 'static> Supervised<T> {
+    /// Spawns a task that runs `follow` over `connection` until it fails,
+    /// then replaces the connection with `connect`, backing off according to
+    /// `policy`, and follows again.
+    ///
+    /// `state` builds the task's state from the sender its output goes to.
+    /// Once the policy gives up, or on an error reconnecting cannot fix, the
+    /// error is sent downstream and the task ends. It also ends when the
+    /// consumer goes away.
+    pub(crate) fn spawn<S, C, Connect, Follow>(
+        policy: ReconnectPolicy,
+        state: impl FnOnce(Outbox<T>) -> S,
+        connection: C,
+        mut connect: Connect,
+        mut follow: Follow,
+    ) -> Self
+    where
+        S: Send + 'static,
+        C: Send + Sync + 'static,
+        Connect: for<'a> FnMut(&'a mut S) -> BoxFuture<'a, Result<C, DwatError>> + Send + 'static,
+        Follow: for<'a> FnMut(&'a mut S, &'a C) -> BoxFuture<'a, DwatError> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(BUFFER);
+        let mut state = state(tx.clone());
+
+        let task = tokio::spawn(async move {
+            let mut connection = connection;
+            loop {
+                let mut err = follow(&mut state, &connection).await;
+                if tx.is_closed() {
+                    return;
+                }
+
+                let mut backoff = policy.backoff();
+                connection = loop {
+                    let delay = match backoff.next_delay() {
+                        Some(delay) if recoverable(&err) => delay,
+                        _ => {
+                            let _ = tx.send(Err(err)).await;
+                            return;
+                        }
+                    };
+                    time::sleep(delay).await;
+                    match connect(&mut state).await {
+                        Ok(connection) => break connection,
+                        Err(e) => err = e,
+                    }
+                };
+            }
+        });
+
+        Self { rx, task }
+    }
+}
+
+impl<T> Stream for Supervised<T> {
+    type Item = Result<T, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Supervised<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Whether a fresh connection can get past `err`.
+fn recoverable(err: &DwatError) -> bool {
+    // Reconnecting can neither recover orphaned history nor make peers that
+    // disagree on a block agree.
+    !matches!(err, DwatError::DeepReorg(_) | DwatError::NoQuorum(_))
+}
@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+
+use ethers::{
+    core::types::{Block, H256},
+    providers::{JsonRpcClient, Middleware, Provider},
+};
+
+use crate::error::DwatError;
+
+/// A change to the canonical chain as seen by the tracker.
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    /// The block became part of the canonical chain.
+    Applied(Block<H256>),
+    /// The block was orphaned by a reorganisation; data derived from it
+    /// should be undone.
+    Reverted(Block<H256>),
+}
+
+impl ChainEvent {
+    pub fn block(&self) -> &Block<H256> {
+        match self {
+            ChainEvent::Applied(block) | ChainEvent::Reverted(block) => block,
+        }
+    }
+}
+
+/// Follows the canonical chain through a window of recent headers.
+///
+/// Each incoming block is linked to the window through its `parent_hash`.
+/// When it does not extend the current tip, the tracker walks the new branch
+/// back to the common ancestor, reverting the orphaned blocks from the tip
+/// down and then applying the new branch from the ancestor up.
+#[derive(Debug)]
+pub struct ReorgTracker {
+    window: VecDeque<Block<H256>>,
+    depth: usize,
+}
+
+impl ReorgTracker {
+    /// Creates a tracker remembering the last `depth` canonical headers,
+    /// which bounds the deepest reorganisation it can resolve.
+    pub fn new(depth: usize) -> Self {
+        Self {
+            window: VecDeque::with_capacity(depth + 1),
+            depth: depth.max(1),
+        }
+    }
+
+    /// Starts tracking from a known canonical block, such as a checkpoint.
+    pub fn seed(&mut self, block: Block<H256>) {
+        self.window.clear();
+        self.push(block);
+    }
+
+    /// The most recent canonical block.
+    pub fn tip(&self) -> Option<&Block<H256>> {
+        self.window.back()
+    }
+
+    /// Links `block` into the window, fetching missing ancestors from
+    /// `provider`, and returns the resulting chain events in order.
+    pub async fn ingest<P: JsonRpcClient>(
+        &mut self,
+        provider: &Provider<P>,
+        block: Block<H256>,
+    ) -> Result<Vec<ChainEvent>, DwatError> {
+        let Some(hash) = block.hash else {
+            return Ok(Vec::new());
+        };
+        if self.position(hash).is_some() {
+            return Ok(Vec::new());
+        }
+
+        let Some(tip) = self.tip() else {
+            self.push(block.clone());
+            return Ok(vec![ChainEvent::Applied(block)]);
+        };
+        if tip.hash == Some(block.parent_hash) {
+            self.push(block.clone());
+            return Ok(vec![ChainEvent::Applied(block)]);
+        }
+
+        let oldest = number(&self.window[0]);
+        let mut branch = vec![block];
+        let ancestor = loop {
+            let cursor = &branch[branch.len() - 1];
+            if let Some(index) = self.position(cursor.parent_hash) {
+                break index;
+            }
+            if number(cursor) <= oldest {
+                return Err(DwatError::DeepReorg(self.depth));
+            }
+
+            let parent = provider
+                .get_block(cursor.parent_hash)
+                .await?
+                .ok_or(DwatError::UnknownBlock(cursor.parent_hash))?;
+            branch.push(parent);
+        };
+
+        let mut events: Vec<ChainEvent> = self
+            .window
+            .drain(ancestor + 1..)
+            .rev()
+            .map(ChainEvent::Reverted)
+            .collect();
+        for block in branch.into_iter().rev() {
+            self.push(block.clone());
+            events.push(ChainEvent::Applied(block));
+        }
+
+        Ok(events)
+    }
+
+    fn position(&self, hash: H256) -> Option<usize> {
+        self.window.iter().position(|b| b.hash == Some(hash))
+    }
+
+    fn push(&mut self, block: Block<H256>) {
+        self.window.push_back(block);
+        while self.window.len() > self.depth {
+            self.window.pop_front();
+        }
+    }
+}
+
+fn number(block: &Block<H256>) -> u64 {
+    block.number.map(|n| n.as_u64()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{core::types::U64, providers::MockProvider};
+
+    use super::*;
+
+    /// Block `number` on `fork`, whose parent is on `parent_fork`.
+    fn block(number: u64, fork: u64, parent_fork: u64) -> Block<H256> {
+        Block {
+            hash: Some(hash(number, fork)),
+            parent_hash: hash(number - 1, parent_fork),
+            number: Some(U64::from(number)),
+            ..Default::default()
+        }
+    }
+
+    fn hash(number: u64, fork: u64) -> H256 {
+        H256::from_low_u64_be(number * 100 + fork)
+    }
+
+    fn tracker(depth: usize, numbers: std::ops::RangeInclusive<u64>) -> ReorgTracker {
+        let mut tracker = ReorgTracker::new(depth);
+        for number in numbers {
+            tracker.push(block(number, 0, 0));
+        }
+        tracker
+    }
+
+    fn hashes(events: &[ChainEvent]) -> Vec<(bool, H256)> {
+        events
+            .iter()
+            .map(|event| {
+                let applied = matches!(event, ChainEvent::Applied(_));
+                (applied, event.block().hash.unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn applies_blocks_extending_the_tip() {
+        let (provider, _) = Provider::mocked();
+        let mut tracker = ReorgTracker::new(4);
+
+        let events = tracker.ingest(&provider, block(1, 0, 0)).await.unwrap();
+        assert_eq!(hashes(&events), [(true, hash(1, 0))]);
+        let events = tracker.ingest(&provider, block(2, 0, 0)).await.unwrap();
+        assert_eq!(hashes(&events), [(true, hash(2, 0))]);
+        assert_eq!(tracker.tip().unwrap().hash, Some(hash(2, 0)));
+    }
+
+    #[tokio::test]
+    async fn ignores_known_blocks() {
+        let (provider, _) = Provider::mocked();
+        let mut tracker = tracker(4, 1..=3);
+
+        let events = tracker.ingest(&provider, block(2, 0, 0)).await.unwrap();
+        assert!(events.is_empty());
+        assert_eq!(tracker.tip().unwrap().hash, Some(hash(3, 0)));
+    }
+
+    #[tokio::test]
+    async fn replaces_a_sibling_of_the_tip() {
+        let (provider, _) = Provider::mocked();
+        let mut tracker = tracker(4, 1..=3);
+
+        let events = tracker.ingest(&provider, block(3, 1, 0)).await.unwrap();
+        assert_eq!(hashes(&events), [(false, hash(3, 0)), (true, hash(3, 1))]);
+        assert_eq!(tracker.tip().unwrap().hash, Some(hash(3, 1)));
+    }
+
+    #[tokio::test]
+    async fn fetches_the_new_branch_back_to_the_ancestor() {
+        let (provider, mock) = Provider::<MockProvider>::mocked();
+        mock.push(block(3, 1, 0)).unwrap();
+        let mut tracker = tracker(4, 1..=3);
+
+        let events = tracker.ingest(&provider, block(4, 1, 1)).await.unwrap();
+        assert_eq!(
+            hashes(&events),
+            [(false, hash(3, 0)), (true, hash(3, 1)), (true, hash(4, 1))]
+        );
+        assert_eq!(tracker.tip().unwrap().hash, Some(hash(4, 1)));
+    }
+
+    #[tokio::test]
+    async fn fails_on_a_reorg_deeper_than_the_window() {
+        let (provider, mock) = Provider::<MockProvider>::mocked();
+        // Responses are served last in, first out.
+        mock.push(block(2, 1, 1)).unwrap();
+        mock.push(block(3, 1, 1)).unwrap();
+        let mut tracker = tracker(2, 1..=3);
+
+        let err = tracker.ingest(&provider, block(4, 1, 1)).await.unwrap_err();
+        assert!(matches!(err, DwatError::DeepReorg(2)));
+    }
+}
@@ -0,0 +1,128 @@
+// Mirrors the tables created by `migrations/`.
+
+diesel::table! {
+    blocks (hash) {
+        hash -> Text,
+        number -> Int8,
+        parent_hash -> Text,
+        timestamp -> Int8,
+        miner -> Nullable<Text>,
+        gas_used -> Int8,
+        gas_limit -> Int8,
+        base_fee_per_gas -> Nullable<Numeric>,
+        transaction_count -> Int4,
+    }
+}
+
+diesel::table! {
+    transactions (hash) {
+        hash -> Text,
+        block_hash -> Text,
+        block_number -> Int8,
+        transaction_index -> Int4,
+        from_address -> Text,
+        to_address -> Nullable<Text>,
+        value -> Numeric,
+        gas -> Numeric,
+        gas_price -> Nullable<Numeric>,
+        nonce -> Numeric,
+        input -> Text,
+    }
+}
+
+diesel::table! {
+    receipts (transaction_hash) {
+        transaction_hash -> Text,
+        block_hash -> Text,
+        block_number -> Int8,
+        status -> Nullable<Int2>,
+        gas_used -> Nullable<Numeric>,
+        cumulative_gas_used -> Numeric,
+        effective_gas_price -> Nullable<Numeric>,
+        contract_address -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    logs (block_hash, log_index) {
+        block_hash -> Text,
+        log_index -> Int4,
+        block_number -> Int8,
+        transaction_hash -> Text,
+        address -> Text,
+        topic0 -> Nullable<Text>,
+        topic1 -> Nullable<Text>,
+        topic2 -> Nullable<Text>,
+        topic3 -> Nullable<Text>,
+        data -> Text,
+    }
+}
+
+diesel::joinable!(transactions -> blocks (block_hash));
+diesel::joinable!(receipts -> transactions (transaction_hash));
+diesel::joinable!(logs -> blocks (block_hash));
+
+diesel::allow_tables_to_appear_in_same_query!(blocks, transactions, receipts, logs);
+
+diesel::table! {
+    checkpoints (name) {
+        name -> Text,
+        block_number -> Int8,
+        block_hash -> Text,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    token_transfers (block_hash, log_index, batch_index) {
+        block_hash -> Text,
+        log_index -> Int4,
+        batch_index -> Int4,
+        block_number -> Int8,
+        transaction_hash -> Text,
+        token -> Text,
+        standard -> Text,
+        operator -> Nullable<Text>,
+        from_address -> Text,
+        to_address -> Text,
+        token_id -> Nullable<Numeric>,
+        value -> Numeric,
+    }
+}
+
+diesel::table! {
+    token_balance_changes (block_hash, holder, token, token_id) {
+        block_hash -> Text,
+        block_number -> Int8,
+        holder -> Text,
+        token -> Text,
+        token_id -> Numeric,
+        delta -> Numeric,
+    }
+}
+
+diesel::table! {
+    token_balances (holder, token, token_id) {
+        holder -> Text,
+        token -> Text,
+        token_id -> Numeric,
+        balance -> Numeric,
+        block_number -> Int8,
+    }
+}
+
+diesel::joinable!(token_transfers -> blocks (block_hash));
+diesel::joinable!(token_balance_changes -> blocks (block_hash));
+
+diesel::allow_tables_to_appear_in_same_query!(blocks, token_transfers, token_balance_changes);
+
+diesel::table! {
+    tokens (address) {
+        address -> Text,
+        name -> Nullable<Text>,
+        symbol -> Nullable<Text>,
+        decimals -> Nullable<Int2>,
+        total_supply -> Nullable<Numeric>,
+        updated_at -> Timestamptz,
+    }
+}
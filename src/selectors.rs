@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use ethers::core::abi::{Function, HumanReadableParser};
+
+/// Common function signatures shipped with `dwat`.
+const SIGNATURES: &str = include_str!("../data/signatures.txt");
+
+static FUNCTIONS: LazyLock<HashMap<[u8; 4], Vec<Function>>> = LazyLock::new(|| {
+    let mut functions: HashMap<[u8; 4], Vec<Function>> = HashMap::new();
+    let parsed = SIGNATURES
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| HumanReadableParser::parse_function(&format!("function {}", line)).ok());
+    for function in parsed {
+        functions
+            .entry(function.short_signature())
+            .or_default()
+            .push(function);
+    }
+    functions
+});
+
+/// Bundled functions with `selector`, usually one. Their parameters have
+/// no names.
+pub fn lookup(selector: [u8; 4]) -> &'static [Function] {
+    FUNCTIONS
+        .get(&selector)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
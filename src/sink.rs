@@ -0,0 +1,117 @@
+use futures::StreamExt;
+use tokio::task;
+
+use crate::checkpoint::{Checkpoint, CheckpointStore};
+use crate::error::DwatError;
+use crate::source::{ChainBlock, ChainSource, ChainUpdate, Head};
+
+/// A destination for canonical chain data from any [`ChainSource`].
+///
+/// Implementations are synchronous; [`index`] calls them from a blocking
+/// section of the runtime.
+pub trait Sink {
+    /// Stores a block that became canonical.
+    fn apply(&mut self, block: &ChainBlock) -> Result<(), DwatError>;
+
+    /// Removes everything stored for a block orphaned by a reorganisation.
+    fn revert(&mut self, head: &Head) -> Result<(), DwatError>;
+
+    /// The header stored for block `number`, if any. Used on restart to walk
+    /// back from a checkpoint that was orphaned while indexing was stopped.
+    fn stored(&mut self, number: u64) -> Result<Option<Head>, DwatError>;
+}
+
+/// Follows the canonical blocks of `source` and writes every change to
+/// `sink`, recording progress in `checkpoints`.
+///
+/// If `checkpoints` holds a position, indexing resumes right after it and
+/// the blocks produced in the meantime are backfilled first. Stored blocks
+/// that were orphaned while indexing was stopped are reverted before that,
+/// up to `reorg_depth` of them. Must run on a multi-threaded runtime.
+pub async fn index<C: ChainSource, S: Sink>(
+    source: &C,
+    sink: &mut S,
+    checkpoints: &mut dyn CheckpointStore,
+    reorg_depth: usize,
+) -> Result<(), DwatError> {
+    let resume_from = match checkpoints.load()? {
+        Some(checkpoint) => Some(rewind(source, sink, checkpoints, checkpoint, reorg_depth).await?),
+        None => None,
+    };
+
+    let mut stream = source.blocks(resume_from).await?;
+
+    while let Some(update) = stream.next().await {
+        match update? {
+            ChainUpdate::Applied(block) => {
+                task::block_in_place(|| {
+                    sink.apply(&block)?;
+                    match block.head.checkpoint() {
+                        Some(checkpoint) => checkpoints.save(&checkpoint),
+                        None => Ok(()),
+                    }
+                })?;
+            }
+            ChainUpdate::Reverted(head) => {
+                task::block_in_place(|| {
+                    sink.revert(&head)?;
+                    match head.parent_checkpoint() {
+                        Some(parent) => checkpoints.save(&parent),
+                        None => Ok(()),
+                    }
+                })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reverts stored blocks from `checkpoint` down until it is canonical again,
+/// returning the checkpoint to resume from. Fails with
+/// [`DwatError::DeepReorg`] if more than `depth` blocks were orphaned.
+///
+/// A source that has no block at the checkpoint's number yet is trusted;
+/// the stream resolves that case once it catches up.
+async fn rewind<C: ChainSource, S: Sink>(
+    source: &C,
+    sink: &mut S,
+    checkpoints: &mut dyn CheckpointStore,
+    mut checkpoint: Checkpoint,
+    depth: usize,
+) -> Result<Checkpoint, DwatError> {
+    for _ in 0..=depth {
+        let canonical = match source
+            .fetch_range(checkpoint.number, checkpoint.number)
+            .await
+        {
+            Ok(blocks) => blocks.into_iter().next(),
+            Err(DwatError::MissingBlock(_)) => None,
+            Err(err) => return Err(err),
+        };
+        match canonical.and_then(|block| block.head.checkpoint()) {
+            Some(canonical) if canonical.hash != checkpoint.hash => {}
+            _ => return Ok(checkpoint),
+        }
+
+        let orphan = task::block_in_place(|| sink.stored(checkpoint.number))?
+            .filter(|head| head.checkpoint() == Some(checkpoint))
+            .ok_or_else(|| {
+                DwatError::Checkpoint(format!(
+                    "block {} was orphaned but is not stored",
+                    checkpoint.number
+                ))
+            })?;
+        let parent = orphan.parent_checkpoint().ok_or_else(|| {
+            DwatError::Checkpoint(format!("invalid parent hash: {}", orphan.parent_hash))
+        })?;
+
+        task::block_in_place(|| {
+            sink.revert(&orphan)?;
+            checkpoints.save(&parent)
+        })?;
+        checkpoint = parent;
+    }
+
+    Err(DwatError::DeepReorg(depth))
+}
@@ -0,0 +1,284 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::join_all;
+use futures::{FutureExt, Stream, StreamExt};
+use serde::Serialize;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+};
+use solana_client::rpc_request::RpcError;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_transaction_status_client_types::option_serializer::OptionSerializer;
+use solana_transaction_status_client_types::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionDetails,
+    UiCompiledInstruction, UiConfirmedBlock, UiInstruction, UiMessage, UiTransactionEncoding,
+    UiTransactionStatusMeta,
+};
+use tokio::time;
+
+use super::SolanaConfig;
+use crate::error::DwatError;
+use crate::reconnect::{Outbox, Supervised};
+
+/// A Solana block with its transactions.
+#[derive(Debug, Clone, Serialize)]
+pub struct SolanaBlock {
+    pub slot: u64,
+    #[serde(flatten)]
+    pub block: UiConfirmedBlock,
+}
+
+/// An instruction of a transaction, in execution order.
+pub(crate) struct ExecutedInstruction<'a> {
+    /// Index of the top-level instruction.
+    pub index: usize,
+    /// Index within the top-level instruction's inner instructions.
+    pub inner_index: Option<usize>,
+    pub instruction: &'a UiCompiledInstruction,
+}
+
+/// A successful transaction's accounts and instructions.
+pub(crate) struct Executed<'a> {
+    pub signature: String,
+    /// Static account keys followed by those loaded from lookup tables, so
+    /// instruction account indices resolve against it.
+    pub keys: Vec<String>,
+    pub instructions: Vec<ExecutedInstruction<'a>>,
+    pub meta: &'a UiTransactionStatusMeta,
+}
+
+impl Executed<'_> {
+    pub fn key(&self, index: u8) -> Option<&str> {
+        self.keys.get(index as usize).map(String::as_str)
+    }
+}
+
+/// Unpacks a JSON-encoded transaction, or returns `None` if it failed or
+/// was fetched in another encoding.
+pub(crate) fn executed(tx: &EncodedTransactionWithStatusMeta) -> Option<Executed<'_>> {
+    let meta = tx.meta.as_ref().filter(|meta| meta.err.is_none())?;
+    let EncodedTransaction::Json(ui) = &tx.transaction else {
+        return None;
+    };
+    let UiMessage::Raw(message) = &ui.message else {
+        return None;
+    };
+
+    let mut keys = message.account_keys.clone();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        keys.extend(loaded.writable.iter().cloned());
+        keys.extend(loaded.readonly.iter().cloned());
+    }
+
+    let inner = match &meta.inner_instructions {
+        OptionSerializer::Some(inner) => inner.as_slice(),
+        _ => &[],
+    };
+    let mut instructions = Vec::new();
+    for (index, instruction) in message.instructions.iter().enumerate() {
+        instructions.push(ExecutedInstruction {
+            index,
+            inner_index: None,
+            instruction,
+        });
+        let invoked = inner
+            .iter()
+            .filter(|inner| inner.index as usize == index)
+            .flat_map(|inner| &inner.instructions);
+        for (inner_index, instruction) in invoked.enumerate() {
+            if let UiInstruction::Compiled(instruction) = instruction {
+                instructions.push(ExecutedInstruction {
+                    index,
+                    inner_index: Some(inner_index),
+                    instruction,
+                });
+            }
+        }
+    }
+
+    Some(Executed {
+        signature: ui.signatures.first().cloned().unwrap_or_default(),
+        keys,
+        instructions,
+        meta,
+    })
+}
+
+/// A gap-free, ordered stream of Solana blocks at the configured commitment.
+///
+/// Slot notifications from `slotSubscribe` drive the stream: on each one
+/// the blocks between the last emitted slot and the node's latest slot at
+/// `config.commitment` are fetched, skipping slots that produced no block.
+/// The subscription is re-established with backoff and the slots missed
+/// meanwhile are caught up the same way, so blocks come out exactly as the
+/// [`BlockStream`](crate::BlockStream) emits EVM blocks.
+pub struct SolanaBlockStream {
+    inner: Supervised<SolanaBlock>,
+}
+
+impl SolanaBlockStream {
+    pub async fn connect(rpc_url: &str) -> Result<Self, DwatError> {
+        Self::connect_with(SolanaConfig::new(rpc_url)).await
+    }
+
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(SolanaConfig::from_env()?).await
+    }
+
+    pub async fn connect_with(config: SolanaConfig) -> Result<Self, DwatError> {
+        if !config.commitment.is_at_least_confirmed() {
+            return Err(DwatError::Config(
+                "Solana blocks can only be fetched at confirmed or finalized commitment".into(),
+            ));
+        }
+        let client = PubsubClient::new(&config.ws_url).await?;
+
+        let inner = Supervised::spawn(
+            config.reconnect.clone(),
+            |tx| SlotSupervisor {
+                rpc: config.rpc(),
+                last: config.resume_from,
+                config,
+                tx,
+            },
+            client,
+            |supervisor| {
+                async move { Ok(PubsubClient::new(&supervisor.config.ws_url).await?) }.boxed()
+            },
+            |supervisor, client| supervisor.follow(client).boxed(),
+        );
+
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for SolanaBlockStream {
+    type Item = Result<SolanaBlock, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// What the node returned for a slot.
+pub(crate) enum Fetched {
+    Block(Box<UiConfirmedBlock>),
+    /// The leader produced no block for the slot.
+    Skipped,
+    /// The block has not reached the commitment yet.
+    Pending,
+}
+
+/// Follows slot notifications for a [`SolanaBlockStream`].
+struct SlotSupervisor {
+    config: SolanaConfig,
+    rpc: RpcClient,
+    tx: Outbox<SolanaBlock>,
+    /// Last slot emitted or skipped.
+    last: Option<u64>,
+}
+
+impl SlotSupervisor {
+    /// Fetches blocks on every slot notification until the subscription
+    /// fails, returning the cause.
+    async fn follow(&mut self, client: &PubsubClient) -> DwatError {
+        let (mut slots, _unsubscribe) = match client.slot_subscribe().await {
+            Ok(sub) => sub,
+            Err(err) => return err.into(),
+        };
+
+        loop {
+            if let Err(err) = self.catch_up().await {
+                return err;
+            }
+
+            let next = match self.config.reconnect.idle_timeout {
+                Some(idle) => match time::timeout(idle, slots.next()).await {
+                    Ok(next) => next,
+                    Err(_) => return DwatError::SubscriptionClosed,
+                },
+                None => slots.next().await,
+            };
+            if next.is_none() {
+                return DwatError::SubscriptionClosed;
+            }
+        }
+    }
+
+    /// Emits the blocks after the last slot up to the latest slot at the
+    /// configured commitment, `config.batch_size` requests at a time.
+    async fn catch_up(&mut self) -> Result<(), DwatError> {
+        let tip = self.rpc.get_slot().await?;
+        let mut next = self.last.map_or(tip, |last| last + 1);
+
+        let batch = self.config.batch_size.max(1) as u64;
+        while next <= tip {
+            let end = (next + batch - 1).min(tip);
+            let commitment = self.config.commitment;
+            let fetched =
+                join_all((next..=end).map(|slot| fetch_block(&self.rpc, slot, commitment))).await;
+
+            for (slot, fetched) in (next..=end).zip(fetched) {
+                match fetched? {
+                    Fetched::Block(block) => {
+                        let block = SolanaBlock {
+                            slot,
+                            block: *block,
+                        };
+                        self.tx
+                            .send(Ok(block))
+                            .await
+                            .map_err(|_| DwatError::SubscriptionClosed)?;
+                    }
+                    Fetched::Skipped => {}
+                    // Retried on the next notification.
+                    Fetched::Pending => return Ok(()),
+                }
+                self.last = Some(slot);
+            }
+            next = end + 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fetches the block at `slot` with full transactions.
+pub(crate) async fn fetch_block(
+    rpc: &RpcClient,
+    slot: u64,
+    commitment: CommitmentConfig,
+) -> Result<Fetched, DwatError> {
+    let config = RpcBlockConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        transaction_details: Some(TransactionDetails::Full),
+        rewards: Some(false),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    };
+    match rpc.get_block_with_config(slot, config).await {
+        Ok(block) => Ok(Fetched::Block(Box::new(block))),
+        Err(err) => match error_code(&err) {
+            Some(
+                JSON_RPC_SERVER_ERROR_SLOT_SKIPPED
+                | JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+            ) => Ok(Fetched::Skipped),
+            Some(JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE) => Ok(Fetched::Pending),
+            _ => Err(err.into()),
+        },
+    }
+}
+
+/// The JSON-RPC error code of a failed request, if the node returned one.
+pub(crate) fn error_code(err: &ClientError) -> Option<i64> {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => Some(*code),
+        _ => None,
+    }
+}
@@ -0,0 +1,936 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use solana_sdk::bs58;
+use solana_sdk::hash::hashv;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status_client_types::option_serializer::OptionSerializer;
+use solana_transaction_status_client_types::EncodedTransactionWithStatusMeta;
+
+use super::blocks::{executed, Executed, ExecutedInstruction, SolanaBlock};
+use crate::error::DwatError;
+
+/// Optional file in an IDL directory mapping program IDs to IDL names.
+pub const PROGRAMS_FILE: &str = "programs.json";
+
+/// Prefix of the instruction Anchor's `emit_cpi!` uses to carry an event.
+const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+/// Nesting depth past which a value is not decoded, to stop recursive types.
+const MAX_DEPTH: usize = 32;
+
+/// Anchor IDLs and the programs they describe.
+///
+/// IDLs are loaded from `<name>.json` files in either the legacy format or
+/// the one introduced with Anchor 0.30. The program ID is taken from the
+/// IDL's `address` (or `metadata.address`), from a file named after the
+/// program ID, or from `programs.json`, an object of
+/// `"<program id>": "<name>"` entries.
+#[derive(Debug, Clone, Default)]
+pub struct IdlRegistry {
+    idls: HashMap<String, Idl>,
+    programs: HashMap<Pubkey, String>,
+}
+
+/// The instructions, events and types of one Anchor program.
+#[derive(Debug, Clone)]
+pub struct Idl {
+    pub name: String,
+    pub address: Option<Pubkey>,
+    instructions: Vec<IdlInstruction>,
+    events: Vec<IdlEvent>,
+    types: HashMap<String, IdlTypeDef>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlInstruction {
+    name: String,
+    discriminator: [u8; 8],
+    accounts: Vec<String>,
+    args: Vec<IdlField>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlEvent {
+    name: String,
+    discriminator: [u8; 8],
+    fields: Vec<IdlField>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlField {
+    name: String,
+    ty: IdlType,
+}
+
+#[derive(Debug, Clone)]
+enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Debug, Clone)]
+enum IdlTypeDef {
+    Struct(IdlFields),
+    Enum(Vec<(String, Option<IdlFields>)>),
+}
+
+/// A Borsh-encoded type as an IDL spells it.
+#[derive(Debug, Clone)]
+enum IdlType {
+    Bool,
+    Unsigned(usize),
+    Signed(usize),
+    F32,
+    F64,
+    String,
+    Bytes,
+    Pubkey,
+    Option(Box<IdlType>),
+    COption(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    Defined(String),
+}
+
+impl fmt::Display for IdlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdlType::Bool => write!(f, "bool"),
+            IdlType::Unsigned(bytes) => write!(f, "u{}", bytes * 8),
+            IdlType::Signed(bytes) => write!(f, "i{}", bytes * 8),
+            IdlType::F32 => write!(f, "f32"),
+            IdlType::F64 => write!(f, "f64"),
+            IdlType::String => write!(f, "string"),
+            IdlType::Bytes => write!(f, "bytes"),
+            IdlType::Pubkey => write!(f, "pubkey"),
+            IdlType::Option(inner) => write!(f, "option<{}>", inner),
+            IdlType::COption(inner) => write!(f, "coption<{}>", inner),
+            IdlType::Vec(inner) => write!(f, "vec<{}>", inner),
+            IdlType::Array(inner, len) => write!(f, "[{}; {}]", inner, len),
+            IdlType::Defined(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Instruction data or an event decoded against a program's IDL.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DecodedActivity {
+    Instruction(DecodedInstruction),
+    Event(DecodedProgramEvent),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedInstruction {
+    pub program: String,
+    pub name: String,
+    pub accounts: Vec<InstructionAccount>,
+    pub args: Vec<DecodedField>,
+    pub slot: u64,
+    pub signature: String,
+    /// Index of the top-level instruction.
+    pub instruction: usize,
+    /// Index within the top-level instruction's inner instructions.
+    pub inner_instruction: Option<usize>,
+}
+
+/// An account passed to an instruction, named as in the IDL. Accounts past
+/// the IDL's list (remaining accounts) have no name.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstructionAccount {
+    pub name: Option<String>,
+    pub pubkey: String,
+}
+
+/// An event a program emitted with `emit!` or `emit_cpi!`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedProgramEvent {
+    pub program: String,
+    pub name: String,
+    pub fields: Vec<DecodedField>,
+    pub slot: u64,
+    pub signature: String,
+}
+
+/// One named instruction argument or event field. Integers are decimal
+/// strings, public keys base58 and byte strings base64.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: Value,
+}
+
+impl IdlRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `*.json` IDL in `dir`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, DwatError> {
+        let dir = dir.as_ref();
+        let mut registry = Self::new();
+        let read_err = |err: std::io::Error| DwatError::Idl(format!("{}: {}", dir.display(), err));
+
+        for entry in fs::read_dir(dir).map_err(read_err)? {
+            let path = entry.map_err(read_err)?.path();
+            let (Some(stem), Some("json")) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|s| s.to_str()),
+            ) else {
+                continue;
+            };
+            if path.file_name().and_then(|s| s.to_str()) == Some(PROGRAMS_FILE) {
+                continue;
+            }
+
+            let bytes = fs::read(&path).map_err(read_err)?;
+            let value: Value = serde_json::from_slice(&bytes)
+                .map_err(|err| DwatError::Idl(format!("{}: {}", path.display(), err)))?;
+            let idl = Idl::from_json(&value)
+                .map_err(|err| DwatError::Idl(format!("{}: {}", path.display(), err)))?;
+
+            if let Some(address) = idl.address.or_else(|| stem.parse().ok()) {
+                registry.programs.insert(address, stem.to_string());
+            }
+            registry.insert(stem, idl);
+        }
+
+        let programs = dir.join(PROGRAMS_FILE);
+        if programs.exists() {
+            let bytes = fs::read(&programs).map_err(read_err)?;
+            let map: HashMap<String, String> = serde_json::from_slice(&bytes)
+                .map_err(|err| DwatError::Idl(format!("{}: {}", programs.display(), err)))?;
+            for (program, name) in map {
+                let program = program
+                    .parse()
+                    .map_err(|_| DwatError::Idl(format!("invalid program ID: {}", program)))?;
+                registry.register(program, &name)?;
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, idl: Idl) {
+        self.idls.insert(name.into(), idl);
+    }
+
+    /// Binds `program` to the IDL called `name`.
+    pub fn register(&mut self, program: Pubkey, name: &str) -> Result<(), DwatError> {
+        if !self.idls.contains_key(name) {
+            return Err(DwatError::Idl(format!("no IDL named {}", name)));
+        }
+        self.programs.insert(program, name.to_string());
+        Ok(())
+    }
+
+    pub fn idl(&self, name: &str) -> Option<&Idl> {
+        self.idls.get(name)
+    }
+
+    pub fn idl_for(&self, program: &Pubkey) -> Option<&Idl> {
+        self.programs
+            .get(program)
+            .and_then(|name| self.idls.get(name))
+    }
+
+    /// Decodes the instructions and events of every successful transaction
+    /// in `block` that involve a registered program.
+    pub fn decode_block(&self, block: &SolanaBlock) -> Vec<DecodedActivity> {
+        block
+            .block
+            .transactions
+            .iter()
+            .flatten()
+            .flat_map(|tx| self.decode_transaction(block.slot, tx))
+            .collect()
+    }
+
+    /// Decodes a transaction's instructions for registered programs, inner
+    /// ones included, followed by the events its programs logged.
+    pub fn decode_transaction(
+        &self,
+        slot: u64,
+        tx: &EncodedTransactionWithStatusMeta,
+    ) -> Vec<DecodedActivity> {
+        let Some(executed) = executed(tx) else {
+            return Vec::new();
+        };
+
+        let mut decoded: Vec<DecodedActivity> = executed
+            .instructions
+            .iter()
+            .filter_map(|instruction| self.decode_instruction(&executed, instruction, slot))
+            .collect();
+
+        if let OptionSerializer::Some(logs) = &executed.meta.log_messages {
+            decoded.extend(
+                self.decode_logs(logs, slot, &executed.signature)
+                    .into_iter()
+                    .map(DecodedActivity::Event),
+            );
+        }
+        decoded
+    }
+
+    fn decode_instruction(
+        &self,
+        executed: &Executed<'_>,
+        executed_instruction: &ExecutedInstruction<'_>,
+        slot: u64,
+    ) -> Option<DecodedActivity> {
+        let instruction = executed_instruction.instruction;
+        let program = executed.key(instruction.program_id_index)?;
+        let idl = self.idl_for(&program.parse().ok()?)?;
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+
+        // Events emitted with `emit_cpi!` travel as a self-invocation.
+        if let Some(event) = data.strip_prefix(&EVENT_IX_TAG) {
+            return idl.decode_event(event).map(|(name, fields)| {
+                DecodedActivity::Event(DecodedProgramEvent {
+                    program: program.to_string(),
+                    name,
+                    fields,
+                    slot,
+                    signature: executed.signature.clone(),
+                })
+            });
+        }
+
+        let (ix, args) = idl.decode_instruction(&data)?;
+        let accounts = instruction
+            .accounts
+            .iter()
+            .enumerate()
+            .filter_map(|(position, &index)| {
+                Some(InstructionAccount {
+                    name: ix.accounts.get(position).cloned(),
+                    pubkey: executed.key(index)?.to_string(),
+                })
+            })
+            .collect();
+
+        Some(DecodedActivity::Instruction(DecodedInstruction {
+            program: program.to_string(),
+            name: ix.name.clone(),
+            accounts,
+            args,
+            slot,
+            signature: executed.signature.clone(),
+            instruction: executed_instruction.index,
+            inner_instruction: executed_instruction.inner_index,
+        }))
+    }
+
+    /// Decodes the `Program data:` lines of a transaction's logs, attributing
+    /// each to the program executing at that point.
+    fn decode_logs(&self, logs: &[String], slot: u64, signature: &str) -> Vec<DecodedProgramEvent> {
+        let mut stack: Vec<&str> = Vec::new();
+        let mut events = Vec::new();
+
+        for line in logs {
+            let Some(rest) = line.strip_prefix("Program ") else {
+                continue;
+            };
+            if let Some(data) = rest.strip_prefix("data: ") {
+                let event = stack
+                    .last()
+                    .and_then(|program| Some((*program, self.idl_for(&program.parse().ok()?)?)))
+                    .and_then(|(program, idl)| {
+                        let bytes = BASE64.decode(data.trim()).ok()?;
+                        let (name, fields) = idl.decode_event(&bytes)?;
+                        Some(DecodedProgramEvent {
+                            program: program.to_string(),
+                            name,
+                            fields,
+                            slot,
+                            signature: signature.to_string(),
+                        })
+                    });
+                events.extend(event);
+            } else if let Some((program, status)) = rest.split_once(' ') {
+                if status.starts_with("invoke [") {
+                    stack.push(program);
+                } else if status == "success" || status.starts_with("failed") {
+                    stack.pop();
+                }
+            }
+        }
+        events
+    }
+}
+
+impl Idl {
+    /// Parses an IDL in the legacy or the Anchor 0.30 format.
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let name = value["name"]
+            .as_str()
+            .or_else(|| value["metadata"]["name"].as_str())
+            .ok_or("IDL has no name")?
+            .to_string();
+        let address = value["address"]
+            .as_str()
+            .or_else(|| value["metadata"]["address"].as_str())
+            .map(|address| {
+                address
+                    .parse()
+                    .map_err(|_| format!("invalid program address: {}", address))
+            })
+            .transpose()?;
+
+        let mut types = HashMap::new();
+        // Legacy IDLs list account structs separately from other types.
+        for def in list(&value["accounts"]).iter().chain(list(&value["types"])) {
+            if let (Some(name), Some(ty)) = (def["name"].as_str(), def.get("type")) {
+                types.insert(name.to_string(), parse_type_def(ty)?);
+            }
+        }
+
+        let instructions = list(&value["instructions"])
+            .iter()
+            .map(|ix| {
+                let name = str_field(ix, "name")?;
+                let discriminator = match ix.get("discriminator") {
+                    Some(bytes) => parse_discriminator(bytes)?,
+                    None => sighash("global", &snake_case(&name)),
+                };
+                let mut accounts = Vec::new();
+                flatten_accounts(list(&ix["accounts"]), &mut accounts);
+                Ok(IdlInstruction {
+                    name,
+                    discriminator,
+                    accounts,
+                    args: parse_fields(list(&ix["args"]))?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let events = list(&value["events"])
+            .iter()
+            .map(|event| {
+                let name = str_field(event, "name")?;
+                let discriminator = match event.get("discriminator") {
+                    Some(bytes) => parse_discriminator(bytes)?,
+                    None => sighash("event", &name),
+                };
+                // Newer IDLs describe event fields under `types`.
+                let fields = match (event.get("fields"), types.get(&name)) {
+                    (Some(fields), _) => parse_fields(list(fields))?,
+                    (None, Some(IdlTypeDef::Struct(IdlFields::Named(fields)))) => fields.clone(),
+                    _ => Vec::new(),
+                };
+                Ok(IdlEvent {
+                    name,
+                    discriminator,
+                    fields,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            name,
+            address,
+            instructions,
+            events,
+            types,
+        })
+    }
+
+    fn decode_instruction(&self, data: &[u8]) -> Option<(&IdlInstruction, Vec<DecodedField>)> {
+        let (discriminator, args) = data.split_at_checked(8)?;
+        let ix = self
+            .instructions
+            .iter()
+            .find(|ix| ix.discriminator == discriminator)?;
+        Some((ix, self.decode_fields(&ix.args, args)?))
+    }
+
+    fn decode_event(&self, data: &[u8]) -> Option<(String, Vec<DecodedField>)> {
+        let (discriminator, fields) = data.split_at_checked(8)?;
+        let event = self
+            .events
+            .iter()
+            .find(|event| event.discriminator == discriminator)?;
+        Some((
+            event.name.clone(),
+            self.decode_fields(&event.fields, fields)?,
+        ))
+    }
+
+    fn decode_fields(&self, fields: &[IdlField], data: &[u8]) -> Option<Vec<DecodedField>> {
+        let mut reader = Reader { data };
+        fields
+            .iter()
+            .map(|field| {
+                Some(DecodedField {
+                    name: field.name.clone(),
+                    kind: field.ty.to_string(),
+                    value: self.decode_value(&field.ty, &mut reader, 0)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads one Borsh value of type `ty` as JSON.
+    fn decode_value(&self, ty: &IdlType, reader: &mut Reader<'_>, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let value = match ty {
+            IdlType::Bool => json!(reader.take(1)?[0] != 0),
+            IdlType::Unsigned(bytes) => {
+                let mut buf = [0; 16];
+                buf[..*bytes].copy_from_slice(reader.take(*bytes)?);
+                json!(u128::from_le_bytes(buf).to_string())
+            }
+            IdlType::Signed(bytes) => {
+                let raw = reader.take(*bytes)?;
+                let fill = if raw[bytes - 1] & 0x80 != 0 { 0xff } else { 0 };
+                let mut buf = [fill; 16];
+                buf[..*bytes].copy_from_slice(raw);
+                json!(i128::from_le_bytes(buf).to_string())
+            }
+            IdlType::F32 => json!(f32::from_le_bytes(reader.take(4)?.try_into().ok()?)),
+            IdlType::F64 => json!(f64::from_le_bytes(reader.take(8)?.try_into().ok()?)),
+            IdlType::String => {
+                let len = reader.len()?;
+                json!(String::from_utf8_lossy(reader.take(len)?))
+            }
+            IdlType::Bytes => {
+                let len = reader.len()?;
+                json!(BASE64.encode(reader.take(len)?))
+            }
+            IdlType::Pubkey => json!(bs58::encode(reader.take(32)?).into_string()),
+            IdlType::Option(inner) => match reader.take(1)?[0] {
+                0 => Value::Null,
+                _ => self.decode_value(inner, reader, depth + 1)?,
+            },
+            IdlType::COption(inner) => match reader.take(4)? {
+                [0, 0, 0, 0] => Value::Null,
+                _ => self.decode_value(inner, reader, depth + 1)?,
+            },
+            IdlType::Vec(inner) => {
+                let len = reader.len()?;
+                self.decode_seq(inner, len, reader, depth)?
+            }
+            IdlType::Array(inner, len) => self.decode_seq(inner, *len, reader, depth)?,
+            IdlType::Defined(name) => match self.types.get(name)? {
+                IdlTypeDef::Struct(fields) => self.decode_struct(fields, reader, depth)?,
+                IdlTypeDef::Enum(variants) => {
+                    let (variant, fields) = variants.get(reader.take(1)?[0] as usize)?;
+                    match fields {
+                        None => json!(variant),
+                        Some(fields) => {
+                            json!({ variant.clone(): self.decode_struct(fields, reader, depth)? })
+                        }
+                    }
+                }
+            },
+        };
+        Some(value)
+    }
+
+    fn decode_seq(
+        &self,
+        ty: &IdlType,
+        len: usize,
+        reader: &mut Reader<'_>,
+        depth: usize,
+    ) -> Option<Value> {
+        // Every element takes at least a byte, which bounds bogus lengths.
+        if len > reader.data.len() {
+            return None;
+        }
+        (0..len)
+            .map(|_| self.decode_value(ty, reader, depth + 1))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array)
+    }
+
+    fn decode_struct(
+        &self,
+        fields: &IdlFields,
+        reader: &mut Reader<'_>,
+        depth: usize,
+    ) -> Option<Value> {
+        match fields {
+            IdlFields::Named(fields) => {
+                let mut object = Map::new();
+                for field in fields {
+                    let value = self.decode_value(&field.ty, reader, depth + 1)?;
+                    object.insert(field.name.clone(), value);
+                }
+                Some(Value::Object(object))
+            }
+            IdlFields::Tuple(types) => types
+                .iter()
+                .map(|ty| self.decode_value(ty, reader, depth + 1))
+                .collect::<Option<Vec<_>>>()
+                .map(Value::Array),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (head, rest) = self.data.split_at_checked(n)?;
+        self.data = rest;
+        Some(head)
+    }
+
+    /// Reads a Borsh `u32` length prefix.
+    fn len(&mut self) -> Option<usize> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?) as usize)
+    }
+}
+
+/// Anchor's 8-byte discriminator: the start of `sha256("<namespace>:<name>")`.
+fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let hash = hashv(&[namespace.as_bytes(), b":", name.as_bytes()]);
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+/// Converts a legacy camelCase instruction name to the snake_case Anchor
+/// hashes.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn list(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn str_field(value: &Value, key: &str) -> Result<String, String> {
+    value[key]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| format!("missing {} in {}", key, value))
+}
+
+fn parse_discriminator(value: &Value) -> Result<[u8; 8], String> {
+    let bytes: Vec<u8> = serde_json::from_value(value.clone())
+        .map_err(|_| format!("invalid discriminator: {}", value))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("invalid discriminator: {}", value))
+}
+
+/// Collects account names, flattening legacy nested account groups.
+fn flatten_accounts(accounts: &[Value], names: &mut Vec<String>) {
+    for account in accounts {
+        match account.get("accounts") {
+            Some(nested) => flatten_accounts(list(nested), names),
+            None => names.extend(account["name"].as_str().map(String::from)),
+        }
+    }
+}
+
+fn parse_fields(fields: &[Value]) -> Result<Vec<IdlField>, String> {
+    fields
+        .iter()
+        .map(|field| {
+            Ok(IdlField {
+                name: str_field(field, "name")?,
+                ty: parse_type(&field["type"])?,
+            })
+        })
+        .collect()
+}
+
+/// Parses struct or variant fields, which are either all named or all
+/// positional.
+fn parse_struct_fields(fields: &[Value]) -> Result<IdlFields, String> {
+    if fields.iter().all(|field| field.get("name").is_some()) {
+        parse_fields(fields).map(IdlFields::Named)
+    } else {
+        fields
+            .iter()
+            .map(parse_type)
+            .collect::<Result<_, _>>()
+            .map(IdlFields::Tuple)
+    }
+}
+
+fn parse_type_def(ty: &Value) -> Result<IdlTypeDef, String> {
+    match ty["kind"].as_str() {
+        Some("struct") => parse_struct_fields(list(&ty["fields"])).map(IdlTypeDef::Struct),
+        Some("enum") => list(&ty["variants"])
+            .iter()
+            .map(|variant| {
+                let fields = variant
+                    .get("fields")
+                    .map(|fields| parse_struct_fields(list(fields)))
+                    .transpose()?;
+                Ok((str_field(variant, "name")?, fields))
+            })
+            .collect::<Result<_, String>>()
+            .map(IdlTypeDef::Enum),
+        _ => Err(format!("unsupported type definition: {}", ty)),
+    }
+}
+
+fn parse_type(ty: &Value) -> Result<IdlType, String> {
+    if let Some(name) = ty.as_str() {
+        return Ok(match name {
+            "bool" => IdlType::Bool,
+            "u8" => IdlType::Unsigned(1),
+            "u16" => IdlType::Unsigned(2),
+            "u32" => IdlType::Unsigned(4),
+            "u64" => IdlType::Unsigned(8),
+            "u128" => IdlType::Unsigned(16),
+            "i8" => IdlType::Signed(1),
+            "i16" => IdlType::Signed(2),
+            "i32" => IdlType::Signed(4),
+            "i64" => IdlType::Signed(8),
+            "i128" => IdlType::Signed(16),
+            "f32" => IdlType::F32,
+            "f64" => IdlType::F64,
+            "string" => IdlType::String,
+            "bytes" => IdlType::Bytes,
+            "publicKey" | "pubkey" => IdlType::Pubkey,
+            _ => return Err(format!("unsupported type: {}", name)),
+        });
+    }
+
+    let inner = |key: &str| parse_type(&ty[key]).map(Box::new);
+    if ty.get("option").is_some() {
+        Ok(IdlType::Option(inner("option")?))
+    } else if ty.get("coption").is_some() {
+        Ok(IdlType::COption(inner("coption")?))
+    } else if ty.get("vec").is_some() {
+        Ok(IdlType::Vec(inner("vec")?))
+    } else if let Some([item, len]) = ty["array"].as_array().map(Vec::as_slice) {
+        let len = len
+            .as_u64()
+            .ok_or_else(|| format!("unsupported array length: {}", len))?;
+        Ok(IdlType::Array(Box::new(parse_type(item)?), len as usize))
+    } else if let Some(defined) = ty.get("defined") {
+        defined
+            .as_str()
+            .or_else(|| defined["name"].as_str())
+            .map(|name| IdlType::Defined(name.to_string()))
+            .ok_or_else(|| format!("unsupported type: {}", ty))
+    } else {
+        Err(format!("unsupported type: {}", ty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A legacy IDL exercising every supported type.
+    fn legacy() -> Idl {
+        Idl::from_json(&json!({
+            "name": "market",
+            "instructions": [{
+                "name": "setConfig",
+                "accounts": [
+                    {"name": "authority"},
+                    {"name": "market", "accounts": [{"name": "state"}, {"name": "vault"}]}
+                ],
+                "args": [
+                    {"name": "amount", "type": "u64"},
+                    {"name": "delta", "type": "i16"},
+                    {"name": "label", "type": "string"},
+                    {"name": "owner", "type": "publicKey"},
+                    {"name": "limit", "type": {"option": "u32"}},
+                    {"name": "tags", "type": {"vec": "u8"}},
+                    {"name": "pair", "type": {"array": ["u16", 2]}},
+                    {"name": "config", "type": {"defined": "Config"}},
+                    {"name": "side", "type": {"defined": "Side"}}
+                ]
+            }],
+            "accounts": [{
+                "name": "Config",
+                "type": {"kind": "struct", "fields": [{"name": "enabled", "type": "bool"}]}
+            }],
+            "types": [{
+                "name": "Side",
+                "type": {"kind": "enum", "variants": [
+                    {"name": "Bid"},
+                    {"name": "Ask", "fields": [{"name": "price", "type": "u64"}]}
+                ]}
+            }],
+            "events": [{
+                "name": "Filled",
+                "fields": [{"name": "size", "type": "u128", "index": false}]
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn values(fields: &[DecodedField]) -> Value {
+        fields
+            .iter()
+            .map(|field| (field.name.clone(), field.value.clone()))
+            .collect::<Map<_, _>>()
+            .into()
+    }
+
+    #[test]
+    fn sighash_matches_anchor() {
+        assert_eq!(
+            sighash("global", "initialize"),
+            [175, 175, 109, 31, 13, 152, 155, 237]
+        );
+    }
+
+    #[test]
+    fn snake_cases_legacy_names() {
+        assert_eq!(snake_case("setConfig"), "set_config");
+        assert_eq!(snake_case("initialize"), "initialize");
+        assert_eq!(snake_case("SwapBaseIn"), "swap_base_in");
+        assert_eq!(snake_case("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn decodes_borsh_instruction_arguments() {
+        let idl = legacy();
+        let owner = Pubkey::new_from_array([7; 32]);
+
+        let mut data = sighash("global", "set_config").to_vec();
+        data.extend(1_000u64.to_le_bytes());
+        data.extend((-2i16).to_le_bytes());
+        data.extend(3u32.to_le_bytes());
+        data.extend(b"abc");
+        data.extend(owner.to_bytes());
+        data.extend([1]);
+        data.extend(9u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        data.extend([4, 5]);
+        data.extend(1u16.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend([1]);
+        data.extend([1]);
+        data.extend(42u64.to_le_bytes());
+
+        let (ix, args) = idl.decode_instruction(&data).unwrap();
+        assert_eq!(ix.name, "setConfig");
+        assert_eq!(ix.accounts, ["authority", "state", "vault"]);
+        assert_eq!(
+            values(&args),
+            json!({
+                "amount": "1000",
+                "delta": "-2",
+                "label": "abc",
+                "owner": owner.to_string(),
+                "limit": "9",
+                "tags": ["4", "5"],
+                "pair": ["1", "2"],
+                "config": {"enabled": true},
+                "side": {"Ask": {"price": "42"}}
+            })
+        );
+        assert_eq!(args[4].kind, "option<u32>");
+
+        // Truncated data does not decode.
+        assert!(idl.decode_instruction(&data[..data.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn decodes_none_and_unit_variants() {
+        let idl = legacy();
+        let mut data = sighash("global", "set_config").to_vec();
+        data.extend([0; 8 + 2]);
+        data.extend(0u32.to_le_bytes());
+        data.extend([0; 32]);
+        data.extend([0]);
+        data.extend(0u32.to_le_bytes());
+        data.extend([0; 4]);
+        data.extend([0]);
+        data.extend([0]);
+
+        let (_, args) = idl.decode_instruction(&data).unwrap();
+        let args = values(&args);
+        assert_eq!(args["limit"], Value::Null);
+        assert_eq!(args["tags"], json!([]));
+        assert_eq!(args["side"], json!("Bid"));
+    }
+
+    #[test]
+    fn decodes_legacy_events() {
+        let mut data = sighash("event", "Filled").to_vec();
+        data.extend(u128::MAX.to_le_bytes());
+
+        let (name, fields) = legacy().decode_event(&data).unwrap();
+        assert_eq!(name, "Filled");
+        assert_eq!(values(&fields), json!({"size": u128::MAX.to_string()}));
+    }
+
+    #[test]
+    fn reads_anchor_030_idls() {
+        let address = Pubkey::new_from_array([3; 32]);
+        let idl = Idl::from_json(&json!({
+            "address": address.to_string(),
+            "metadata": {"name": "vault"},
+            "instructions": [{
+                "name": "deposit",
+                "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                "accounts": [{"name": "user"}],
+                "args": [{"name": "amount", "type": "u64"}]
+            }],
+            "events": [{"name": "Deposited", "discriminator": [8, 7, 6, 5, 4, 3, 2, 1]}],
+            "types": [{
+                "name": "Deposited",
+                "type": {"kind": "struct", "fields": [{"name": "user", "type": "pubkey"}]}
+            }]
+        }))
+        .unwrap();
+        assert_eq!(idl.name, "vault");
+        assert_eq!(idl.address, Some(address));
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(5u64.to_le_bytes());
+        let (ix, args) = idl.decode_instruction(&data).unwrap();
+        assert_eq!(ix.name, "deposit");
+        assert_eq!(values(&args), json!({"amount": "5"}));
+
+        let mut data = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        data.extend(address.to_bytes());
+        let (name, fields) = idl.decode_event(&data).unwrap();
+        assert_eq!(name, "Deposited");
+        assert_eq!(values(&fields), json!({"user": address.to_string()}));
+    }
+
+    #[test]
+    fn rejects_bogus_lengths() {
+        let idl = Idl::from_json(&json!({
+            "name": "lengths",
+            "instructions": [{
+                "name": "run",
+                "accounts": [],
+                "args": [{"name": "items", "type": {"vec": "u64"}}]
+            }]
+        }))
+        .unwrap();
+        let mut data = sighash("global", "run").to_vec();
+        data.extend(u32::MAX.to_le_bytes());
+        assert!(idl.decode_instruction(&data).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+pub mod blocks;
+pub mod idl;
+pub mod subscriptions;
+pub mod token;
+pub mod transfers;
+
+pub use blocks::{SolanaBlock, SolanaBlockStream};
+pub use idl::{DecodedActivity, Idl, IdlRegistry};
+pub use subscriptions::{SolanaEvent, WatchConfig, WatchStream};
+pub use token::{TokenAccount, TokenInstruction};
+pub use transfers::{token_events, TokenEvent, TokenEventKind};
+
+use std::env;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::config::{parse_var, DEFAULT_BATCH_SIZE};
+use crate::error::DwatError;
+use crate::reconnect::ReconnectPolicy;
+
+/// Settings for the Solana sources.
+#[derive(Debug, Clone)]
+pub struct SolanaConfig {
+    /// HTTP JSON-RPC endpoint, used to fetch blocks and accounts.
+    pub rpc_url: String,
+    /// PubSub WebSocket endpoint, used for subscriptions.
+    pub ws_url: String,
+    /// Commitment blocks must reach before they are emitted.
+    pub commitment: CommitmentConfig,
+    pub reconnect: ReconnectPolicy,
+    /// Last slot already processed; the stream starts right after it.
+    pub resume_from: Option<u64>,
+    /// Blocks fetched concurrently while catching up.
+    pub batch_size: usize,
+}
+
+impl SolanaConfig {
+    /// Uses `rpc_url` with the WebSocket endpoint a validator serves next to
+    /// it (the same host, with the port one higher when one is given).
+    pub fn new(rpc_url: impl Into<String>) -> Self {
+        let rpc_url = rpc_url.into();
+        Self {
+            ws_url: websocket_url(&rpc_url),
+            rpc_url,
+            commitment: CommitmentConfig::confirmed(),
+            reconnect: ReconnectPolicy::default(),
+            resume_from: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Reads `SOLANA_RPC_ENDPOINT` and the optional `SOLANA_WS_ENDPOINT`,
+    /// `SOLANA_COMMITMENT` and `BACKFILL_BATCH`.
+    pub fn from_env() -> Result<Self, DwatError> {
+        let rpc_url = env::var("SOLANA_RPC_ENDPOINT").map_err(|_| {
+            DwatError::Config("SOLANA_RPC_ENDPOINT must be set in environment".into())
+        })?;
+
+        let mut config = Self::new(rpc_url);
+        if let Ok(ws_url) = env::var("SOLANA_WS_ENDPOINT") {
+            config.ws_url = ws_url;
+        }
+        if let Some(commitment) = parse_var("SOLANA_COMMITMENT")? {
+            config.commitment = commitment;
+        }
+        if let Some(batch_size) = parse_var("BACKFILL_BATCH")? {
+            config.batch_size = batch_size;
+        }
+        Ok(config)
+    }
+
+    pub(crate) fn rpc(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+}
+
+/// Derives the PubSub URL a validator serves alongside `rpc_url`.
+fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>().ok().and_then(|p| p.checked_add(1)) {
+            Some(port) => format!("{}:{}", host, port),
+            None => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+
+    if path.is_empty() {
+        format!("{}://{}", scheme, authority)
+    } else {
+        format!("{}://{}/{}", scheme, authority, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_the_pubsub_url() {
+        assert_eq!(
+            websocket_url("http://127.0.0.1:8899"),
+            "ws://127.0.0.1:8900"
+        );
+        assert_eq!(
+            websocket_url("https://api.mainnet-beta.solana.com"),
+            "wss://api.mainnet-beta.solana.com"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com:443/key/abc"),
+            "wss://rpc.example.com:444/key/abc"
+        );
+        assert_eq!(websocket_url("localhost:8899"), "ws://localhost:8900");
+        assert_eq!(websocket_url("http://[::1]:8899"), "ws://[::1]:8900");
+    }
+
+    #[test]
+    fn keeps_ports_it_cannot_increment() {
+        assert_eq!(websocket_url("http://host:65535"), "ws://host:65535");
+        assert_eq!(websocket_url("http://host:rpc"), "ws://host:rpc");
+    }
+}
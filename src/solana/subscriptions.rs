@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::{select_all, BoxStream};
+use futures::{FutureExt, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use solana_account_decoder_client_types::{UiAccount, UiAccountData, UiAccountEncoding};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{Response, RpcLogsResponse};
+use solana_sdk::pubkey::Pubkey;
+
+use super::token::TokenAccount;
+use super::SolanaConfig;
+use crate::config::parse_list_var;
+use crate::error::DwatError;
+use crate::reconnect::{Outbox, Supervised};
+
+/// Accounts per `getMultipleAccounts` request.
+const ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Settings for a [`WatchStream`].
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub solana: SolanaConfig,
+    /// Accounts followed with `accountSubscribe`.
+    pub accounts: Vec<Pubkey>,
+    /// Addresses whose transactions' logs are followed with
+    /// `logsSubscribe`, usually programs.
+    pub mentions: Vec<Pubkey>,
+}
+
+impl WatchConfig {
+    pub fn new(solana: SolanaConfig) -> Self {
+        Self {
+            solana,
+            accounts: Vec::new(),
+            mentions: Vec::new(),
+        }
+    }
+
+    /// Reads the Solana settings plus the comma-separated `SOLANA_ACCOUNTS`
+    /// and `SOLANA_MENTIONS`.
+    pub fn from_env() -> Result<Self, DwatError> {
+        let mut config = Self::new(SolanaConfig::from_env()?);
+        if let Some(accounts) = parse_list_var("SOLANA_ACCOUNTS")? {
+            config.accounts = accounts;
+        }
+        if let Some(mentions) = parse_list_var("SOLANA_MENTIONS")? {
+            config.mentions = mentions;
+        }
+        Ok(config)
+    }
+}
+
+/// A change reported by a Solana subscription.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SolanaEvent {
+    Account(AccountUpdate),
+    Logs(ProgramLogs),
+}
+
+/// The new state of a watched account.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountUpdate {
+    pub pubkey: String,
+    pub slot: u64,
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: AccountData,
+}
+
+/// Account data, decoded when the layout is known.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "encoding", content = "value", rename_all = "snake_case")]
+pub enum AccountData {
+    /// An SPL Token or Token-2022 token account.
+    SplToken(TokenAccount),
+    Base64(String),
+}
+
+/// The logs of a transaction that mentions a watched address.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramLogs {
+    /// The watched address the transaction mentions.
+    pub mention: String,
+    pub slot: u64,
+    pub signature: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub logs: Vec<String>,
+}
+
+/// A stream of account changes and program logs from a Solana validator.
+///
+/// Every account in `config.accounts` gets an `accountSubscribe` and every
+/// address in `config.mentions` a `logsSubscribe` with a mentions filter,
+/// all over one PubSub connection. The connection is re-established with
+/// backoff; after each (re)connect the watched accounts are read once so a
+/// change made during an outage is still reported. Logs emitted while
+/// disconnected are not replayed.
+pub struct WatchStream {
+    inner: Supervised<SolanaEvent>,
+}
+
+impl WatchStream {
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(WatchConfig::from_env()?).await
+    }
+
+    pub async fn connect_with(config: WatchConfig) -> Result<Self, DwatError> {
+        if config.accounts.is_empty() && config.mentions.is_empty() {
+            return Err(DwatError::Config(
+                "no Solana accounts or mentions to watch".into(),
+            ));
+        }
+        let client = PubsubClient::new(&config.solana.ws_url).await?;
+
+        let inner = Supervised::spawn(
+            config.solana.reconnect.clone(),
+            |tx| Watcher {
+                rpc: config.solana.rpc(),
+                config,
+                tx,
+                slots: HashMap::new(),
+            },
+            client,
+            |watcher| {
+                async move { Ok(PubsubClient::new(&watcher.config.solana.ws_url).await?) }.boxed()
+            },
+            |watcher, client| watcher.follow(client).boxed(),
+        );
+
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for WatchStream {
+    type Item = Result<SolanaEvent, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+enum Notification {
+    Account(Pubkey, Response<UiAccount>),
+    Logs(Pubkey, Response<RpcLogsResponse>),
+}
+
+/// Follows the subscriptions of a [`WatchStream`].
+struct Watcher {
+    config: WatchConfig,
+    rpc: RpcClient,
+    tx: Outbox<SolanaEvent>,
+    /// Slot of the last reported state of each account.
+    slots: HashMap<Pubkey, u64>,
+}
+
+impl Watcher {
+    /// Forwards notifications until a subscription fails, returning the
+    /// cause.
+    async fn follow(&mut self, client: &PubsubClient) -> DwatError {
+        let mut streams: Vec<BoxStream<'_, Notification>> = Vec::new();
+        for &pubkey in &self.config.accounts {
+            match client
+                .account_subscribe(&pubkey, Some(self.account_config()))
+                .await
+            {
+                Ok((stream, _)) => streams.push(
+                    stream
+                        .map(move |update| Notification::Account(pubkey, update))
+                        .boxed(),
+                ),
+                Err(err) => return err.into(),
+            }
+        }
+        for &mention in &self.config.mentions {
+            let filter = RpcTransactionLogsFilter::Mentions(vec![mention.to_string()]);
+            let config = RpcTransactionLogsConfig {
+                commitment: Some(self.config.solana.commitment),
+            };
+            match client.logs_subscribe(filter, config).await {
+                Ok((stream, _)) => streams.push(
+                    stream
+                        .map(move |logs| Notification::Logs(mention, logs))
+                        .boxed(),
+                ),
+                Err(err) => return err.into(),
+            }
+        }
+
+        if let Err(err) = self.refresh().await {
+            return err;
+        }
+
+        let mut notifications = select_all(streams);
+        while let Some(notification) = notifications.next().await {
+            let event = match notification {
+                Notification::Account(pubkey, update) => {
+                    self.account(pubkey, update.context.slot, update.value)
+                }
+                Notification::Logs(mention, logs) => Some(logs_event(mention, logs)),
+            };
+            if let Some(event) = event {
+                if let Err(err) = self.send(event).await {
+                    return err;
+                }
+            }
+        }
+        DwatError::SubscriptionClosed
+    }
+
+    /// Reads every watched account and reports those that changed since the
+    /// last update.
+    async fn refresh(&mut self) -> Result<(), DwatError> {
+        let accounts = self.config.accounts.clone();
+        for chunk in accounts.chunks(ACCOUNTS_PER_REQUEST) {
+            let keys: Vec<String> = chunk.iter().map(ToString::to_string).collect();
+            let response: Response<Vec<Option<UiAccount>>> = self
+                .rpc
+                .send(
+                    RpcRequest::GetMultipleAccounts,
+                    json!([keys, self.account_config()]),
+                )
+                .await?;
+
+            let slot = response.context.slot;
+            for (&pubkey, account) in chunk.iter().zip(response.value) {
+                let Some(account) = account else {
+                    continue;
+                };
+                if let Some(event) = self.account(pubkey, slot, account) {
+                    self.send(event).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the update for `account`, unless an update at the same or a
+    /// later slot was already reported.
+    fn account(&mut self, pubkey: Pubkey, slot: u64, account: UiAccount) -> Option<SolanaEvent> {
+        if self.slots.get(&pubkey).is_some_and(|&last| last >= slot) {
+            return None;
+        }
+        self.slots.insert(pubkey, slot);
+
+        let blob = match &account.data {
+            UiAccountData::Binary(blob, UiAccountEncoding::Base64) => blob.clone(),
+            _ => String::new(),
+        };
+        let token = account
+            .owner
+            .parse::<Pubkey>()
+            .ok()
+            .zip(account.data.decode())
+            .and_then(|(owner, data)| TokenAccount::unpack(&owner, &data));
+        let data = match token {
+            Some(token) => AccountData::SplToken(token),
+            None => AccountData::Base64(blob),
+        };
+
+        Some(SolanaEvent::Account(AccountUpdate {
+            pubkey: pubkey.to_string(),
+            slot,
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data,
+        }))
+    }
+
+    fn account_config(&self) -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: Some(self.config.solana.commitment),
+            min_context_slot: None,
+        }
+    }
+
+    async fn send(&self, event: SolanaEvent) -> Result<(), DwatError> {
+        self.tx
+            .send(Ok(event))
+            .await
+            .map_err(|_| DwatError::SubscriptionClosed)
+    }
+}
+
+fn logs_event(mention: Pubkey, logs: Response<RpcLogsResponse>) -> SolanaEvent {
+    SolanaEvent::Logs(ProgramLogs {
+        mention: mention.to_string(),
+        slot: logs.context.slot,
+        signature: logs.value.signature,
+        success: logs.value.err.is_none(),
+        error: logs.value.err.map(|err| err.to_string()),
+        logs: logs.value.logs,
+    })
+}
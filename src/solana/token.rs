@@ -0,0 +1,254 @@
+use serde::Serialize;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+/// The SPL Token program.
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// The Token-2022 program, whose accounts share the SPL Token layout
+/// followed by extensions.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of an SPL Token account without extensions.
+const ACCOUNT_LEN: usize = 165;
+
+/// Account-type byte Token-2022 writes after the base layout of a token
+/// account.
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+pub fn is_token_program(program: &Pubkey) -> bool {
+    *program == TOKEN_PROGRAM_ID || *program == TOKEN_2022_PROGRAM_ID
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAccountState {
+    Uninitialized,
+    Initialized,
+    Frozen,
+}
+
+/// A token account owned by the SPL Token or Token-2022 program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenAccount {
+    pub mint: String,
+    pub owner: String,
+    /// Balance in the mint's base units.
+    pub amount: u64,
+    pub delegate: Option<String>,
+    pub state: TokenAccountState,
+    /// Rent-exempt reserve of a wrapped SOL account.
+    pub is_native: Option<u64>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<String>,
+}
+
+impl TokenAccount {
+    /// Decodes the data of an account owned by `program`, or returns `None`
+    /// if it is not a token account.
+    pub fn unpack(program: &Pubkey, data: &[u8]) -> Option<Self> {
+        let is_account = if *program == TOKEN_PROGRAM_ID {
+            data.len() == ACCOUNT_LEN
+        } else if *program == TOKEN_2022_PROGRAM_ID {
+            data.len() == ACCOUNT_LEN
+                || (data.len() > ACCOUNT_LEN && data[ACCOUNT_LEN] == ACCOUNT_TYPE_ACCOUNT)
+        } else {
+            false
+        };
+        if !is_account {
+            return None;
+        }
+
+        let state = match data[108] {
+            0 => TokenAccountState::Uninitialized,
+            1 => TokenAccountState::Initialized,
+            2 => TokenAccountState::Frozen,
+            _ => return None,
+        };
+        Some(Self {
+            mint: pubkey_at(data, 0).to_string(),
+            owner: pubkey_at(data, 32).to_string(),
+            amount: u64_at(data, 64),
+            delegate: is_set(data, 72).then(|| pubkey_at(data, 76).to_string()),
+            state,
+            is_native: is_set(data, 109).then(|| u64_at(data, 113)),
+            delegated_amount: u64_at(data, 121),
+            close_authority: is_set(data, 129).then(|| pubkey_at(data, 133).to_string()),
+        })
+    }
+}
+
+/// An SPL Token or Token-2022 instruction that moves or authorises a
+/// balance. Both programs share these encodings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenInstruction {
+    Transfer { amount: u64 },
+    Approve { amount: u64 },
+    MintTo { amount: u64 },
+    Burn { amount: u64 },
+    TransferChecked { amount: u64, decimals: u8 },
+    ApproveChecked { amount: u64, decimals: u8 },
+    MintToChecked { amount: u64, decimals: u8 },
+    BurnChecked { amount: u64, decimals: u8 },
+}
+
+impl TokenInstruction {
+    /// Decodes instruction data, or returns `None` for other instructions.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let (&tag, rest) = data.split_first()?;
+        let amount = || (rest.len() >= 8).then(|| u64_at(rest, 0));
+        let decimals = || rest.get(8).copied();
+
+        let instruction = match tag {
+            3 => TokenInstruction::Transfer { amount: amount()? },
+            4 => TokenInstruction::Approve { amount: amount()? },
+            7 => TokenInstruction::MintTo { amount: amount()? },
+            8 => TokenInstruction::Burn { amount: amount()? },
+            12 => TokenInstruction::TransferChecked {
+                amount: amount()?,
+                decimals: decimals()?,
+            },
+            13 => TokenInstruction::ApproveChecked {
+                amount: amount()?,
+                decimals: decimals()?,
+            },
+            14 => TokenInstruction::MintToChecked {
+                amount: amount()?,
+                decimals: decimals()?,
+            },
+            15 => TokenInstruction::BurnChecked {
+                amount: amount()?,
+                decimals: decimals()?,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+}
+
+pub(crate) fn pubkey_at(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+pub(crate) fn u64_at(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Whether the `COption` whose 4-byte tag is at `offset` holds a value.
+fn is_set(data: &[u8], offset: usize) -> bool {
+    data[offset..offset + 4] == [1, 0, 0, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    /// An initialized account of 500 units of mint 1 owned by 2, with
+    /// delegate 3 allowed 40 of them.
+    fn account() -> Vec<u8> {
+        let mut data = vec![0; ACCOUNT_LEN];
+        data[0..32].copy_from_slice(key(1).as_ref());
+        data[32..64].copy_from_slice(key(2).as_ref());
+        data[64..72].copy_from_slice(&500u64.to_le_bytes());
+        data[72..76].copy_from_slice(&[1, 0, 0, 0]);
+        data[76..108].copy_from_slice(key(3).as_ref());
+        data[108] = 1;
+        data[121..129].copy_from_slice(&40u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn unpacks_a_token_account() {
+        let account = TokenAccount::unpack(&TOKEN_PROGRAM_ID, &account()).unwrap();
+        assert_eq!(
+            account,
+            TokenAccount {
+                mint: key(1).to_string(),
+                owner: key(2).to_string(),
+                amount: 500,
+                delegate: Some(key(3).to_string()),
+                state: TokenAccountState::Initialized,
+                is_native: None,
+                delegated_amount: 40,
+                close_authority: None,
+            }
+        );
+    }
+
+    #[test]
+    fn unpacks_token_2022_accounts_with_extensions() {
+        let mut data = account();
+        data.extend([ACCOUNT_TYPE_ACCOUNT, 0, 0, 0, 0]);
+        assert!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data).is_some());
+        // The SPL Token program has no extensions.
+        assert!(TokenAccount::unpack(&TOKEN_PROGRAM_ID, &data).is_none());
+
+        // A mint padded to the same length carries a different type byte.
+        data[ACCOUNT_LEN] = 1;
+        assert!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data).is_none());
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let mut data = account();
+        assert!(TokenAccount::unpack(&key(9), &data).is_none());
+        assert!(TokenAccount::unpack(&TOKEN_PROGRAM_ID, &data[..82]).is_none());
+        data[108] = 3;
+        assert!(TokenAccount::unpack(&TOKEN_PROGRAM_ID, &data).is_none());
+    }
+
+    #[test]
+    fn unpacks_instructions() {
+        let data = |tag: u8, amount: u64, decimals: &[u8]| {
+            let mut data = vec![tag];
+            data.extend(amount.to_le_bytes());
+            data.extend(decimals);
+            data
+        };
+
+        assert_eq!(
+            TokenInstruction::unpack(&data(3, 7, &[])),
+            Some(TokenInstruction::Transfer { amount: 7 })
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&data(8, 7, &[])),
+            Some(TokenInstruction::Burn { amount: 7 })
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&data(12, 7, &[6])),
+            Some(TokenInstruction::TransferChecked {
+                amount: 7,
+                decimals: 6
+            })
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&data(14, 7, &[9])),
+            Some(TokenInstruction::MintToChecked {
+                amount: 7,
+                decimals: 9
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_other_or_truncated_instructions() {
+        assert_eq!(TokenInstruction::unpack(&[]), None);
+        // InitializeMint and CloseAccount.
+        assert_eq!(TokenInstruction::unpack(&[0; 67]), None);
+        assert_eq!(TokenInstruction::unpack(&[9]), None);
+        assert_eq!(TokenInstruction::unpack(&[3, 1, 0, 0]), None);
+        // TransferChecked without its decimals.
+        assert_eq!(
+            TokenInstruction::unpack(&[12, 1, 0, 0, 0, 0, 0, 0, 0]),
+            None
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use serde::Serialize;
+use solana_sdk::bs58;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status_client_types::option_serializer::OptionSerializer;
+use solana_transaction_status_client_types::{
+    EncodedTransactionWithStatusMeta, UiCompiledInstruction, UiTransactionTokenBalance,
+};
+
+use super::blocks::{executed, SolanaBlock};
+use super::token::{is_token_program, TokenInstruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEventKind {
+    Transfer,
+    Mint,
+    Burn,
+    Approve,
+}
+
+/// A balance change or approval made by an SPL Token or Token-2022
+/// instruction, the Solana counterpart of an ERC-20 event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenEvent {
+    pub kind: TokenEventKind,
+    /// The token program that executed the instruction.
+    pub program: String,
+    pub mint: Option<String>,
+    pub decimals: Option<u8>,
+    /// Token account debited by a transfer or burn, or the approved account.
+    pub source: Option<String>,
+    /// Token account credited by a transfer or mint, or the approved delegate.
+    pub destination: Option<String>,
+    pub source_owner: Option<String>,
+    pub destination_owner: Option<String>,
+    /// Signer that authorised the instruction.
+    pub authority: Option<String>,
+    pub raw_amount: u64,
+    /// `raw_amount` scaled by the mint's decimals, when they are known.
+    pub amount: Option<BigDecimal>,
+    pub slot: u64,
+    pub signature: String,
+    /// Index of the top-level instruction.
+    pub instruction: usize,
+    /// Index within the top-level instruction's inner instructions, for
+    /// instructions invoked by another program.
+    pub inner_instruction: Option<usize>,
+}
+
+/// Decodes the token events of every successful transaction in `block`.
+pub fn token_events(block: &SolanaBlock) -> Vec<TokenEvent> {
+    block
+        .block
+        .transactions
+        .iter()
+        .flatten()
+        .flat_map(|tx| transaction_token_events(block.slot, tx))
+        .collect()
+}
+
+/// Decodes the token events of one transaction, top-level and inner
+/// instructions alike, in execution order. Failed transactions have none.
+///
+/// Mints and decimals come from the instruction where it names them and
+/// otherwise from the transaction's token balances.
+pub fn transaction_token_events(
+    slot: u64,
+    tx: &EncodedTransactionWithStatusMeta,
+) -> Vec<TokenEvent> {
+    let Some(executed) = executed(tx) else {
+        return Vec::new();
+    };
+
+    // Post balances win; pre balances cover accounts closed by the
+    // transaction.
+    let mut balances = HashMap::new();
+    for list in [
+        &executed.meta.pre_token_balances,
+        &executed.meta.post_token_balances,
+    ] {
+        if let OptionSerializer::Some(list) = list {
+            for balance in list {
+                balances.insert(balance.account_index as usize, balance);
+            }
+        }
+    }
+
+    let context = Context {
+        keys: &executed.keys,
+        balances: &balances,
+        slot,
+        signature: executed.signature.clone(),
+    };
+    executed
+        .instructions
+        .iter()
+        .filter_map(|executed| {
+            context.decode(executed.instruction, executed.index, executed.inner_index)
+        })
+        .collect()
+}
+
+/// Accounts touched by one instruction, as indices into the transaction's
+/// account keys.
+struct Accounts {
+    mint: Option<usize>,
+    source: Option<usize>,
+    destination: Option<usize>,
+    authority: Option<usize>,
+}
+
+struct Context<'a> {
+    keys: &'a [String],
+    balances: &'a HashMap<usize, &'a UiTransactionTokenBalance>,
+    slot: u64,
+    signature: String,
+}
+
+impl Context<'_> {
+    fn decode(
+        &self,
+        instruction: &UiCompiledInstruction,
+        index: usize,
+        inner_index: Option<usize>,
+    ) -> Option<TokenEvent> {
+        let program = self.keys.get(instruction.program_id_index as usize)?;
+        if !is_token_program(&program.parse::<Pubkey>().ok()?) {
+            return None;
+        }
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+        let decoded = TokenInstruction::unpack(&data)?;
+
+        let account = |n: usize| instruction.accounts.get(n).map(|&i| i as usize);
+        let (kind, raw_amount, decimals, accounts) = match decoded {
+            TokenInstruction::Transfer { amount } => (
+                TokenEventKind::Transfer,
+                amount,
+                None,
+                Accounts {
+                    mint: None,
+                    source: account(0),
+                    destination: account(1),
+                    authority: account(2),
+                },
+            ),
+            TokenInstruction::TransferChecked { amount, decimals } => (
+                TokenEventKind::Transfer,
+                amount,
+                Some(decimals),
+                Accounts {
+                    mint: account(1),
+                    source: account(0),
+                    destination: account(2),
+                    authority: account(3),
+                },
+            ),
+            TokenInstruction::Approve { amount } => (
+                TokenEventKind::Approve,
+                amount,
+                None,
+                Accounts {
+                    mint: None,
+                    source: account(0),
+                    destination: account(1),
+                    authority: account(2),
+                },
+            ),
+            TokenInstruction::ApproveChecked { amount, decimals } => (
+                TokenEventKind::Approve,
+                amount,
+                Some(decimals),
+                Accounts {
+                    mint: account(1),
+                    source: account(0),
+                    destination: account(2),
+                    authority: account(3),
+                },
+            ),
+            TokenInstruction::MintTo { amount } => (
+                TokenEventKind::Mint,
+                amount,
+                None,
+                Accounts {
+                    mint: account(0),
+                    source: None,
+                    destination: account(1),
+                    authority: account(2),
+                },
+            ),
+            TokenInstruction::MintToChecked { amount, decimals } => (
+                TokenEventKind::Mint,
+                amount,
+                Some(decimals),
+                Accounts {
+                    mint: account(0),
+                    source: None,
+                    destination: account(1),
+                    authority: account(2),
+                },
+            ),
+            TokenInstruction::Burn { amount } => (
+                TokenEventKind::Burn,
+                amount,
+                None,
+                Accounts {
+                    mint: account(1),
+                    source: account(0),
+                    destination: None,
+                    authority: account(2),
+                },
+            ),
+            TokenInstruction::BurnChecked { amount, decimals } => (
+                TokenEventKind::Burn,
+                amount,
+                Some(decimals),
+                Accounts {
+                    mint: account(1),
+                    source: account(0),
+                    destination: None,
+                    authority: account(2),
+                },
+            ),
+        };
+
+        // The delegate of an approval is a wallet, not a token account.
+        let token_accounts = match kind {
+            TokenEventKind::Approve => [accounts.source, None],
+            _ => [accounts.source, accounts.destination],
+        };
+        let balance = token_accounts
+            .into_iter()
+            .flatten()
+            .find_map(|i| self.balances.get(&i));
+        let mint = accounts
+            .mint
+            .and_then(|i| self.key(i))
+            .or_else(|| balance.map(|balance| balance.mint.clone()));
+        let decimals = decimals.or_else(|| balance.map(|b| b.ui_token_amount.decimals));
+
+        Some(TokenEvent {
+            kind,
+            program: program.clone(),
+            mint,
+            decimals,
+            source: accounts.source.and_then(|i| self.key(i)),
+            destination: accounts.destination.and_then(|i| self.key(i)),
+            source_owner: token_accounts[0].and_then(|i| self.owner(i)),
+            destination_owner: token_accounts[1].and_then(|i| self.owner(i)),
+            authority: accounts.authority.and_then(|i| self.key(i)),
+            raw_amount,
+            amount: decimals
+                .map(|decimals| BigDecimal::new(BigInt::from(raw_amount), decimals as i64)),
+            slot: self.slot,
+            signature: self.signature.clone(),
+            instruction: index,
+            inner_instruction: inner_index,
+        })
+    }
+
+    fn key(&self, index: usize) -> Option<String> {
+        self.keys.get(index).cloned()
+    }
+
+    fn owner(&self, index: usize) -> Option<String> {
+        match &self.balances.get(&index)?.owner {
+            OptionSerializer::Some(owner) => Some(owner.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::token::TOKEN_PROGRAM_ID;
+    use super::*;
+
+    fn data(tag: u8, amount: u64) -> String {
+        let mut data = vec![tag];
+        data.extend(amount.to_le_bytes());
+        bs58::encode(data).into_string()
+    }
+
+    fn balance(account: usize, mint: &str, owner: &str, amount: &str) -> serde_json::Value {
+        json!({
+            "accountIndex": account,
+            "mint": mint,
+            "owner": owner,
+            "programId": TOKEN_PROGRAM_ID.to_string(),
+            "uiTokenAmount": {
+                "amount": amount,
+                "decimals": 6,
+                "uiAmount": null,
+                "uiAmountString": "0",
+            },
+        })
+    }
+
+    #[test]
+    fn decodes_inner_instructions_with_mints_from_token_balances() {
+        let mut keys: Vec<String> = (0..7).map(|_| Pubkey::new_unique().to_string()).collect();
+        keys[4] = TOKEN_PROGRAM_ID.to_string();
+        let (payer, source, destination, mint, closed) =
+            (&keys[0], &keys[1], &keys[2], &keys[3], &keys[6]);
+        let (alice, bob) = (
+            Pubkey::new_unique().to_string(),
+            Pubkey::new_unique().to_string(),
+        );
+
+        // A swap program (key 5) moves tokens through an inner `Transfer`;
+        // a top-level `Burn` then empties an account that the transaction
+        // closes, so only its pre balance is left.
+        let tx: EncodedTransactionWithStatusMeta = serde_json::from_value(json!({
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    "header": {
+                        "numRequiredSignatures": 1,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 3,
+                    },
+                    "accountKeys": keys,
+                    "recentBlockhash": Pubkey::default().to_string(),
+                    "instructions": [
+                        { "programIdIndex": 5, "accounts": [1, 2, 0, 4], "data": "" },
+                        { "programIdIndex": 4, "accounts": [6, 3, 0], "data": data(8, 250) },
+                    ],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "innerInstructions": [{
+                    "index": 0,
+                    "instructions": [
+                        { "programIdIndex": 4, "accounts": [1, 2, 0], "data": data(3, 1_500_000) },
+                    ],
+                }],
+                "preTokenBalances": [
+                    balance(1, mint, &alice, "2000000"),
+                    balance(6, mint, &alice, "250"),
+                ],
+                "postTokenBalances": [
+                    balance(1, mint, &alice, "500000"),
+                    balance(2, mint, &bob, "1500000"),
+                ],
+            },
+        }))
+        .unwrap();
+
+        let events = transaction_token_events(42, &tx);
+        assert_eq!(events.len(), 2);
+
+        let transfer = &events[0];
+        assert_eq!(transfer.kind, TokenEventKind::Transfer);
+        assert_eq!(
+            (transfer.instruction, transfer.inner_instruction),
+            (0, Some(0))
+        );
+        assert_eq!(transfer.mint.as_ref(), Some(mint));
+        assert_eq!(transfer.decimals, Some(6));
+        assert_eq!(transfer.amount, Some("1.5".parse().unwrap()));
+        assert_eq!(transfer.source.as_ref(), Some(source));
+        assert_eq!(transfer.destination.as_ref(), Some(destination));
+        assert_eq!(transfer.source_owner, Some(alice.clone()));
+        assert_eq!(transfer.destination_owner, Some(bob));
+        assert_eq!(transfer.authority.as_ref(), Some(payer));
+        assert_eq!((transfer.slot, transfer.signature.as_str()), (42, "sig"));
+
+        let burn = &events[1];
+        assert_eq!(burn.kind, TokenEventKind::Burn);
+        assert_eq!((burn.instruction, burn.inner_instruction), (1, None));
+        assert_eq!(burn.mint.as_ref(), Some(mint));
+        assert_eq!(burn.decimals, Some(6));
+        assert_eq!(burn.source.as_ref(), Some(closed));
+        assert_eq!(burn.source_owner, Some(alice));
+        assert_eq!(burn.raw_amount, 250);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use async_trait::async_trait;
+use ethers::{
+    core::types::{Block, Transaction, TransactionReceipt, H256},
+    providers::{Middleware, Provider},
+};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tokio::sync::Mutex;
+
+use super::{Chain, ChainBlock, ChainSource, ChainTransaction, ChainUpdate, Head, NativeBlock};
+use crate::block::{BlockData, BlockFetcher};
+use crate::checkpoint::Checkpoint;
+use crate::config::StreamConfig;
+use crate::endpoints::EndpointPool;
+use crate::error::DwatError;
+use crate::models::hex;
+use crate::stream::{BlockStream, FullBlockStream, FullEvent};
+use crate::transport::Transport;
+
+/// A [`ChainSource`] for EVM chains, over the endpoints in a
+/// [`StreamConfig`]. Requests fail over between the endpoints like the
+/// streams do.
+pub struct EvmSource {
+    config: StreamConfig,
+    pool: Mutex<EndpointPool>,
+    fetcher: BlockFetcher,
+}
+
+impl EvmSource {
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect(StreamConfig::from_env()?).await
+    }
+
+    /// Runs `request` on the healthiest endpoint, moving on to the next one
+    /// each time the endpoint fails, until every endpoint had a turn.
+    async fn request<T, F, Fut>(&self, request: F) -> Result<T, DwatError>
+    where
+        F: Fn(Provider<Transport>) -> Fut,
+        Fut: Future<Output = Result<T, DwatError>>,
+    {
+        let attempts = self.pool.lock().await.len();
+        let mut last = DwatError::Config("no endpoints configured".into());
+        for _ in 0..attempts {
+            let (index, provider) = self.pool.lock().await.connect().await?;
+            match request(provider).await {
+                Err(err @ DwatError::Provider(_)) => {
+                    self.pool.lock().await.failed(index);
+                    last = err;
+                }
+                result => return result,
+            }
+        }
+        Err(last)
+    }
+}
+
+#[async_trait]
+impl ChainSource for EvmSource {
+    type Config = StreamConfig;
+
+    async fn connect(config: StreamConfig) -> Result<Self, DwatError> {
+        let mut pool = EndpointPool::from_config(&config);
+        pool.connect().await?;
+        Ok(Self {
+            config,
+            pool: Mutex::new(pool),
+            fetcher: BlockFetcher::new(),
+        })
+    }
+
+    fn chain(&self) -> Chain {
+        Chain::Evm
+    }
+
+    async fn heads(&self) -> Result<BoxStream<'static, Result<Head, DwatError>>, DwatError> {
+        let stream = BlockStream::connect_with(self.config.clone()).await?;
+        Ok(stream.map_ok(|block| head(&block)).boxed())
+    }
+
+    async fn blocks(
+        &self,
+        resume_from: Option<Checkpoint>,
+    ) -> Result<BoxStream<'static, Result<ChainUpdate, DwatError>>, DwatError> {
+        let config = StreamConfig {
+            resume_from,
+            ..self.config.clone()
+        };
+        let stream = FullBlockStream::connect_with(config).await?;
+        Ok(stream
+            .map_ok(|event| match event {
+                FullEvent::Applied(data) => ChainUpdate::Applied(block(data)),
+                FullEvent::Reverted(header) => ChainUpdate::Reverted(head(&header)),
+            })
+            .boxed())
+    }
+
+    async fn fetch_range(&self, from: u64, to: u64) -> Result<Vec<ChainBlock>, DwatError> {
+        self.request(|provider| async move {
+            stream::iter(from..=to)
+                .map(|number| self.fetcher.fetch(&provider, number))
+                .buffered(self.config.batch_size.max(1))
+                .map_ok(block)
+                .try_collect()
+                .await
+        })
+        .await
+    }
+
+    async fn fetch_transaction(&self, id: &str) -> Result<Option<ChainTransaction>, DwatError> {
+        let hash: H256 = id
+            .parse()
+            .map_err(|_| DwatError::Config(format!("invalid transaction hash: {}", id)))?;
+        self.request(|provider| async move {
+            let Some(tx) = provider.get_transaction(hash).await? else {
+                return Ok(None);
+            };
+            let receipt = provider.get_transaction_receipt(hash).await?;
+            Ok(Some(transaction(&tx, receipt.as_ref())))
+        })
+        .await
+    }
+}
+
+fn head<T>(block: &Block<T>) -> Head {
+    let number = block.number.unwrap_or_default().as_u64();
+    Head {
+        chain: Chain::Evm,
+        number,
+        hash: hex(block.hash.unwrap_or_default()),
+        parent_number: number.checked_sub(1),
+        parent_hash: hex(block.parent_hash),
+        timestamp: Some(block.timestamp.as_u64() as i64),
+    }
+}
+
+impl From<BlockData> for ChainBlock {
+    fn from(data: BlockData) -> Self {
+        block(data)
+    }
+}
+
+/// Normalises `data`, keeping it as the native block.
+fn block(data: BlockData) -> ChainBlock {
+    let receipts: HashMap<H256, &TransactionReceipt> = data
+        .receipts
+        .iter()
+        .map(|receipt| (receipt.transaction_hash, receipt))
+        .collect();
+    let transactions = data
+        .block
+        .transactions
+        .iter()
+        .map(|tx| transaction(tx, receipts.get(&tx.hash).copied()))
+        .collect();
+    ChainBlock {
+        head: head(&data.block),
+        transactions,
+        native: NativeBlock::Evm(Box::new(data)),
+    }
+}
+
+fn transaction(tx: &Transaction, receipt: Option<&TransactionReceipt>) -> ChainTransaction {
+    let fee = receipt.and_then(|receipt| {
+        let price = receipt.effective_gas_price.or(tx.gas_price)?;
+        Some(receipt.gas_used? * price)
+    });
+    ChainTransaction {
+        chain: Chain::Evm,
+        id: hex(tx.hash),
+        block_number: tx.block_number.map(|number| number.as_u64()),
+        block_hash: tx.block_hash.map(hex),
+        index: tx.transaction_index.map(|index| index.as_u64()),
+        from: Some(hex(tx.from)),
+        to: tx.to.map(hex),
+        value: Some(tx.value.to_string()),
+        fee: fee.map(|fee| fee.to_string()),
+        success: receipt
+            .and_then(|receipt| receipt.status)
+            .map(|status| status.as_u64() == 1),
+    }
+}
@@ -0,0 +1,175 @@
+pub mod evm;
+pub mod solana;
+
+pub use evm::EvmSource;
+pub use solana::SolanaSource;
+
+use std::fmt;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use ethers::core::types::H256;
+use futures::stream::BoxStream;
+use serde::Serialize;
+use solana_sdk::hash::Hash;
+
+use crate::block::BlockData;
+use crate::checkpoint::Checkpoint;
+use crate::error::DwatError;
+use crate::solana::SolanaBlock;
+
+/// The chains a [`ChainSource`] can ingest from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chain {
+    Evm,
+    Solana,
+}
+
+impl FromStr for Chain {
+    type Err = DwatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "evm" => Ok(Chain::Evm),
+            "solana" => Ok(Chain::Solana),
+            _ => Err(DwatError::Config(format!("unknown chain: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chain::Evm => write!(f, "evm"),
+            Chain::Solana => write!(f, "solana"),
+        }
+    }
+}
+
+/// A block header in chain-neutral terms. `number` is the block number on
+/// EVM chains and the slot on Solana; hashes are rendered as each chain
+/// writes them.
+#[derive(Debug, Clone, Serialize)]
+pub struct Head {
+    pub chain: Chain,
+    pub number: u64,
+    pub hash: String,
+    pub parent_number: Option<u64>,
+    pub parent_hash: String,
+    /// Unix time in seconds, when the chain records one.
+    pub timestamp: Option<i64>,
+}
+
+impl Head {
+    /// The position of this block, or `None` if its hash is malformed.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        Some(Checkpoint {
+            number: self.number,
+            hash: parse_hash(self.chain, &self.hash)?,
+        })
+    }
+
+    /// The position of the parent block, e.g. to resume from once this one
+    /// is reverted.
+    pub fn parent_checkpoint(&self) -> Option<Checkpoint> {
+        Some(Checkpoint {
+            number: self
+                .parent_number
+                .unwrap_or_else(|| self.number.saturating_sub(1)),
+            hash: parse_hash(self.chain, &self.parent_hash)?,
+        })
+    }
+}
+
+/// A block with its transactions in chain-neutral terms.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainBlock {
+    #[serde(flatten)]
+    pub head: Head,
+    pub transactions: Vec<ChainTransaction>,
+    /// The block as the chain delivered it.
+    #[serde(skip)]
+    pub native: NativeBlock,
+}
+
+/// A block in its chain's own data model, for consumers that need more than
+/// the normalised view, such as logs and receipts.
+#[derive(Debug, Clone)]
+pub enum NativeBlock {
+    Evm(Box<BlockData>),
+    Solana(Box<SolanaBlock>),
+}
+
+/// A change to the canonical chain in chain-neutral terms.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "block", rename_all = "snake_case")]
+pub enum ChainUpdate {
+    /// The block became canonical.
+    Applied(ChainBlock),
+    /// The block was orphaned by a reorganisation.
+    Reverted(Head),
+}
+
+/// A transaction in chain-neutral terms. Amounts are decimal strings in the
+/// chain's base unit (wei, lamports).
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainTransaction {
+    pub chain: Chain,
+    /// Transaction hash on EVM chains, first signature on Solana.
+    pub id: String,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<String>,
+    /// Position within the block.
+    pub index: Option<u64>,
+    /// Sender, or fee payer on Solana.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub value: Option<String>,
+    pub fee: Option<String>,
+    /// Whether execution succeeded, once known.
+    pub success: Option<bool>,
+}
+
+/// A chain the indexer can ingest from.
+///
+/// Implementations hide the transport and data model of their chain behind
+/// the normalised [`Head`], [`ChainBlock`] and [`ChainTransaction`] types, so
+/// consumers written against this trait work for any chain.
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    type Config: Send;
+
+    async fn connect(config: Self::Config) -> Result<Self, DwatError>
+    where
+        Self: Sized;
+
+    fn chain(&self) -> Chain;
+
+    /// Follows new heads, reconnecting as the chain's streams do.
+    async fn heads(&self) -> Result<BoxStream<'static, Result<Head, DwatError>>, DwatError>;
+
+    /// Follows canonical blocks with their transactions, starting right
+    /// after `resume_from` if given, and reports blocks orphaned by
+    /// reorganisations.
+    async fn blocks(
+        &self,
+        resume_from: Option<Checkpoint>,
+    ) -> Result<BoxStream<'static, Result<ChainUpdate, DwatError>>, DwatError>;
+
+    /// Fetches the blocks from `from` to `to` inclusive, in order. Slots
+    /// without a block are left out.
+    async fn fetch_range(&self, from: u64, to: u64) -> Result<Vec<ChainBlock>, DwatError>;
+
+    /// Looks up a transaction by hash or signature.
+    async fn fetch_transaction(&self, id: &str) -> Result<Option<ChainTransaction>, DwatError>;
+}
+
+/// Decodes a block hash as `chain` renders it: hex on EVM chains, base58 on
+/// Solana.
+fn parse_hash(chain: Chain, hash: &str) -> Option<H256> {
+    match chain {
+        Chain::Evm => hash.parse().ok(),
+        Chain::Solana => hash.parse::<Hash>().ok().map(|hash| H256(hash.to_bytes())),
+    }
+}
@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::signature::Signature;
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    EncodedTransactionWithStatusMeta, UiConfirmedBlock, UiMessage, UiTransactionEncoding,
+};
+
+use super::{Chain, ChainBlock, ChainSource, ChainTransaction, ChainUpdate, Head, NativeBlock};
+use crate::checkpoint::Checkpoint;
+use crate::error::DwatError;
+use crate::solana::blocks::{fetch_block, Fetched};
+use crate::solana::{SolanaBlock, SolanaBlockStream, SolanaConfig};
+
+/// A [`ChainSource`] for Solana. Block numbers are slots.
+pub struct SolanaSource {
+    config: SolanaConfig,
+    rpc: RpcClient,
+}
+
+impl SolanaSource {
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect(SolanaConfig::from_env()?).await
+    }
+}
+
+#[async_trait]
+impl ChainSource for SolanaSource {
+    type Config = SolanaConfig;
+
+    async fn connect(config: SolanaConfig) -> Result<Self, DwatError> {
+        let rpc = config.rpc();
+        rpc.get_version().await?;
+        Ok(Self { config, rpc })
+    }
+
+    fn chain(&self) -> Chain {
+        Chain::Solana
+    }
+
+    async fn heads(&self) -> Result<BoxStream<'static, Result<Head, DwatError>>, DwatError> {
+        let stream = SolanaBlockStream::connect_with(self.config.clone()).await?;
+        Ok(stream
+            .map_ok(|block| head(block.slot, &block.block))
+            .boxed())
+    }
+
+    /// Blocks at `confirmed` or `finalized` commitment are not expected to
+    /// be orphaned, so only applied blocks are reported.
+    async fn blocks(
+        &self,
+        resume_from: Option<Checkpoint>,
+    ) -> Result<BoxStream<'static, Result<ChainUpdate, DwatError>>, DwatError> {
+        let config = SolanaConfig {
+            resume_from: resume_from.map(|checkpoint| checkpoint.number),
+            ..self.config.clone()
+        };
+        let stream = SolanaBlockStream::connect_with(config).await?;
+        Ok(stream
+            .map_ok(|block| ChainUpdate::Applied(self::block(block)))
+            .boxed())
+    }
+
+    async fn fetch_range(&self, from: u64, to: u64) -> Result<Vec<ChainBlock>, DwatError> {
+        let commitment = self.config.commitment;
+        let fetched: Vec<(u64, Fetched)> = stream::iter(from..=to)
+            .map(|slot| async move {
+                let fetched = fetch_block(&self.rpc, slot, commitment).await?;
+                Ok::<_, DwatError>((slot, fetched))
+            })
+            .buffered(self.config.batch_size.max(1))
+            .try_collect()
+            .await?;
+
+        fetched
+            .into_iter()
+            .filter_map(|(slot, fetched)| match fetched {
+                Fetched::Block(block) => Some(Ok(self::block(SolanaBlock {
+                    slot,
+                    block: *block,
+                }))),
+                Fetched::Skipped => None,
+                Fetched::Pending => Some(Err(DwatError::MissingBlock(slot))),
+            })
+            .collect()
+    }
+
+    async fn fetch_transaction(&self, id: &str) -> Result<Option<ChainTransaction>, DwatError> {
+        let signature: Signature = id
+            .parse()
+            .map_err(|_| DwatError::Config(format!("invalid signature: {}", id)))?;
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Json),
+            commitment: Some(self.config.commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx: Option<EncodedConfirmedTransactionWithStatusMeta> = self
+            .rpc
+            .send(
+                RpcRequest::GetTransaction,
+                json!([signature.to_string(), config]),
+            )
+            .await?;
+
+        Ok(tx.map(|tx| transaction(&tx.transaction, tx.slot, None, None)))
+    }
+}
+
+fn head(slot: u64, block: &UiConfirmedBlock) -> Head {
+    Head {
+        chain: Chain::Solana,
+        number: slot,
+        hash: block.blockhash.clone(),
+        parent_number: Some(block.parent_slot),
+        parent_hash: block.previous_blockhash.clone(),
+        timestamp: block.block_time,
+    }
+}
+
+impl From<SolanaBlock> for ChainBlock {
+    fn from(block: SolanaBlock) -> Self {
+        self::block(block)
+    }
+}
+
+/// Normalises `block`, keeping it as the native block.
+fn block(block: SolanaBlock) -> ChainBlock {
+    let transactions = block.block.transactions.as_deref().unwrap_or_default();
+    ChainBlock {
+        head: head(block.slot, &block.block),
+        transactions: transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                transaction(
+                    tx,
+                    block.slot,
+                    Some(&block.block.blockhash),
+                    Some(index as u64),
+                )
+            })
+            .collect(),
+        native: NativeBlock::Solana(Box::new(block)),
+    }
+}
+
+fn transaction(
+    tx: &EncodedTransactionWithStatusMeta,
+    slot: u64,
+    blockhash: Option<&str>,
+    index: Option<u64>,
+) -> ChainTransaction {
+    let (id, fee_payer) = match &tx.transaction {
+        EncodedTransaction::Json(ui) => {
+            let fee_payer = match &ui.message {
+                UiMessage::Raw(message) => message.account_keys.first().cloned(),
+                UiMessage::Parsed(message) => {
+                    message.account_keys.first().map(|key| key.pubkey.clone())
+                }
+            };
+            (
+                ui.signatures.first().cloned().unwrap_or_default(),
+                fee_payer,
+            )
+        }
+        other => (
+            other
+                .decode()
+                .and_then(|tx| tx.signatures.first().map(ToString::to_string))
+                .unwrap_or_default(),
+            None,
+        ),
+    };
+
+    ChainTransaction {
+        chain: Chain::Solana,
+        id,
+        block_number: Some(slot),
+        block_hash: blockhash.map(String::from),
+        index,
+        from: fee_payer,
+        to: None,
+        value: None,
+        fee: tx.meta.as_ref().map(|meta| meta.fee.to_string()),
+        success: tx.meta.as_ref().map(|meta| meta.err.is_none()),
+    }
+}
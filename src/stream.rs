@@ -0,0 +1,474 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use ethers::{
+    core::types::{Block, H256},
+    providers::{Middleware, Provider, StreamExt},
+};
+use futures::future::{self, try_join_all, BoxFuture};
+use futures::{FutureExt, Stream};
+use serde::Serialize;
+use tokio::time;
+
+use crate::block::{BlockData, BlockFetcher};
+use crate::config::StreamConfig;
+use crate::delivery::DeliveryGate;
+use crate::endpoints::EndpointPool;
+use crate::error::DwatError;
+use crate::reconnect::{Outbox, Supervised};
+use crate::reorg::{ChainEvent, ReorgTracker};
+use crate::transport::Transport;
+
+/// Number of delivered block hashes remembered for deduplication.
+const RECENT: usize = 128;
+
+/// How long lagging endpoints get to agree on a block in quorum mode.
+const QUORUM_WAIT: Duration = Duration::from_secs(12);
+
+/// Pause between quorum checks for the same block.
+const QUORUM_RETRY: Duration = Duration::from_millis(500);
+
+/// A gap-free, ordered stream of new block headers from a node.
+///
+/// Heads come from a `newHeads` subscription on WebSocket and IPC endpoints
+/// and from polling `eth_blockNumber` on HTTP ones. The subscription runs on
+/// a background task that reconnects with exponential backoff when the
+/// socket drops, failing over to the healthiest of `config.fallbacks`, and
+/// fetches any blocks missed during the outage. With `config.quorum` set,
+/// each block is only forwarded once that many endpoints report the same
+/// hash for it; if they do not agree in time the stream ends with
+/// [`DwatError::NoQuorum`]. When `config.resume_from` is set, the blocks
+/// after that checkpoint are backfilled before live heads are forwarded.
+/// The task is aborted when the stream is dropped.
+///
+/// Blocks are forwarded as the node announces them; use [`ChainStream`] to
+/// be told about reorganisations.
+pub struct BlockStream {
+    inner: Supervised<ChainEvent>,
+}
+
+impl BlockStream {
+    /// Connects to `url` and subscribes to new heads using the default
+    /// reconnection policy.
+    pub async fn connect(url: &str) -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::new(url)).await
+    }
+
+    /// Connects to the endpoint configured in the environment.
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::from_env()?).await
+    }
+
+    /// Connects and subscribes to new heads, reconnecting according to
+    /// `config.reconnect`.
+    ///
+    /// Only the initial connection error is returned here; later failures are
+    /// retried and reported on the stream once the policy gives up.
+    pub async fn connect_with(config: StreamConfig) -> Result<Self, DwatError> {
+        let inner = Supervisor::spawn(config, None).await?;
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for BlockStream {
+    type Item = Result<Block<H256>, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Without a tracker the supervisor only ever applies blocks.
+        self.inner.poll_next_unpin(cx).map(|event| {
+            event.map(|event| {
+                event.map(|event| match event {
+                    ChainEvent::Applied(block) | ChainEvent::Reverted(block) => block,
+                })
+            })
+        })
+    }
+}
+
+/// A stream of canonical chain changes.
+///
+/// Like [`BlockStream`], but every head is checked against a window of
+/// recent headers so that reorganisations surface as
+/// [`ChainEvent::Reverted`] events for the orphaned blocks followed by
+/// [`ChainEvent::Applied`] events for the new branch. Applied blocks are
+/// released according to the configured [`DeliveryMode`](crate::DeliveryMode).
+pub struct ChainStream {
+    inner: Supervised<ChainEvent>,
+}
+
+impl ChainStream {
+    pub async fn connect(url: &str) -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::new(url)).await
+    }
+
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::from_env()?).await
+    }
+
+    pub async fn connect_with(config: StreamConfig) -> Result<Self, DwatError> {
+        let tracker = ReorgTracker::new(config.reorg_depth);
+        let gate = DeliveryGate::new(config.delivery);
+        let inner = Supervisor::spawn(config, Some((tracker, gate))).await?;
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for ChainStream {
+    type Item = Result<ChainEvent, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// A change to the canonical chain carrying full block data.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "block", rename_all = "snake_case")]
+pub enum FullEvent {
+    /// The block, its transactions and their receipts became canonical.
+    Applied(BlockData),
+    /// The block was orphaned by a reorganisation.
+    Reverted(Block<H256>),
+}
+
+/// A [`ChainStream`] that fetches every applied block with its
+/// transactions and receipts before handing it on.
+///
+/// Blocks are fetched from the endpoint the stream follows, so a failed
+/// fetch reconnects and fails over like a dropped subscription and the
+/// block is fetched again afterwards.
+pub struct FullBlockStream {
+    inner: Supervised<FullEvent>,
+}
+
+impl FullBlockStream {
+    pub async fn connect(url: &str) -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::new(url)).await
+    }
+
+    pub async fn from_env() -> Result<Self, DwatError> {
+        Self::connect_with(StreamConfig::from_env()?).await
+    }
+
+    pub async fn connect_with(config: StreamConfig) -> Result<Self, DwatError> {
+        let tracker = ReorgTracker::new(config.reorg_depth);
+        let gate = DeliveryGate::new(config.delivery);
+        let inner = Supervisor::spawn(config, Some((tracker, gate))).await?;
+        Ok(Self { inner })
+    }
+}
+
+impl Stream for FullBlockStream {
+    type Item = Result<FullEvent, DwatError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// What a [`Supervisor`] sends downstream for each chain event.
+trait Output: Sized + Send + 'static {
+    /// Builds the item for `event`, fetching anything else it needs from
+    /// `provider`.
+    fn prepare<'a>(
+        event: &'a ChainEvent,
+        provider: &'a Provider<Transport>,
+        fetcher: &'a BlockFetcher,
+    ) -> BoxFuture<'a, Result<Self, DwatError>>;
+}
+
+impl Output for ChainEvent {
+    fn prepare<'a>(
+        event: &'a ChainEvent,
+        _: &'a Provider<Transport>,
+        _: &'a BlockFetcher,
+    ) -> BoxFuture<'a, Result<Self, DwatError>> {
+        future::ready(Ok(event.clone())).boxed()
+    }
+}
+
+impl Output for FullEvent {
+    fn prepare<'a>(
+        event: &'a ChainEvent,
+        provider: &'a Provider<Transport>,
+        fetcher: &'a BlockFetcher,
+    ) -> BoxFuture<'a, Result<Self, DwatError>> {
+        async move {
+            match event {
+                ChainEvent::Applied(block) => {
+                    let hash = block.hash.unwrap_or_default();
+                    Ok(FullEvent::Applied(fetcher.fetch(provider, hash).await?))
+                }
+                ChainEvent::Reverted(block) => Ok(FullEvent::Reverted(block.clone())),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Follows new heads for a [`BlockStream`], [`ChainStream`] or
+/// [`FullBlockStream`].
+struct Supervisor<T> {
+    config: StreamConfig,
+    tx: Outbox<T>,
+    pool: EndpointPool,
+    /// Index of the endpoint currently followed.
+    active: usize,
+    /// Number of the last block handed downstream.
+    last: Option<u64>,
+    /// Hashes of recently delivered blocks, to drop repeated announcements.
+    recent: VecDeque<H256>,
+    /// Reorg tracking and delivery gating, for [`ChainStream`]s.
+    canonical: Option<(ReorgTracker, DeliveryGate)>,
+    /// Events released but not yet sent, oldest first.
+    pending: VecDeque<ChainEvent>,
+    fetcher: BlockFetcher,
+}
+
+impl<T: Output> Supervisor<T> {
+    async fn spawn(
+        config: StreamConfig,
+        canonical: Option<(ReorgTracker, DeliveryGate)>,
+    ) -> Result<Supervised<T>, DwatError> {
+        let mut pool = EndpointPool::from_config(&config);
+        let (active, provider) = pool.connect().await?;
+
+        let mut canonical = canonical;
+        let mut recent = VecDeque::with_capacity(RECENT + 1);
+        let last = config.resume_from.map(|checkpoint| {
+            if let Some((tracker, _)) = &mut canonical {
+                tracker.seed(checkpoint.header());
+            }
+            recent.push_back(checkpoint.hash);
+            checkpoint.number
+        });
+
+        Ok(Supervised::spawn(
+            config.reconnect.clone(),
+            |tx| Supervisor {
+                config,
+                tx,
+                pool,
+                active,
+                last,
+                recent,
+                canonical,
+                pending: VecDeque::new(),
+                fetcher: BlockFetcher::new(),
+            },
+            provider,
+            |supervisor| supervisor.reconnect().boxed(),
+            |supervisor, provider| {
+                async move {
+                    let err = supervisor.follow(provider).await;
+                    // Peers disagreeing says nothing about the active endpoint.
+                    if !matches!(err, DwatError::NoQuorum(_)) {
+                        supervisor.pool.failed(supervisor.active);
+                    }
+                    err
+                }
+                .boxed()
+            },
+        ))
+    }
+
+    /// Forwards heads until the subscription fails, returning the cause.
+    async fn follow(&mut self, provider: &Provider<Transport>) -> DwatError {
+        if let Err(err) = self.flush(provider).await {
+            return err;
+        }
+        if !provider.as_ref().is_pubsub() {
+            return self.poll(provider).await;
+        }
+
+        let mut sub = match provider.subscribe_blocks().await {
+            Ok(sub) => sub,
+            Err(err) => return err.into(),
+        };
+
+        // Heads announced while catching up queue on the subscription.
+        if let Err(err) = self.catch_up(provider).await {
+            return err;
+        }
+
+        loop {
+            let next = match self.config.reconnect.idle_timeout {
+                Some(idle) => match time::timeout(idle, sub.next()).await {
+                    Ok(next) => next,
+                    Err(_) => return DwatError::SubscriptionClosed,
+                },
+                None => sub.next().await,
+            };
+
+            let Some(block) = next else {
+                return DwatError::SubscriptionClosed;
+            };
+
+            if let Err(err) = self.deliver(provider, block).await {
+                return err;
+            }
+        }
+    }
+
+    /// Polls `eth_blockNumber` every `config.poll_interval` and forwards new
+    /// heads until a request fails or the head stops moving for longer than
+    /// the idle timeout.
+    async fn poll(&mut self, provider: &Provider<Transport>) -> DwatError {
+        let mut interval = time::interval(self.config.poll_interval);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        let mut progressed = Instant::now();
+
+        loop {
+            interval.tick().await;
+            let head = match provider.get_block_number().await {
+                Ok(head) => head.as_u64(),
+                Err(err) => return err.into(),
+            };
+
+            if self.last.is_some_and(|last| head <= last) {
+                if let Some(idle) = self.config.reconnect.idle_timeout {
+                    if progressed.elapsed() > idle {
+                        return DwatError::SubscriptionClosed;
+                    }
+                }
+                continue;
+            }
+            progressed = Instant::now();
+
+            let block = match provider.get_block(head).await {
+                Ok(Some(block)) => block,
+                Ok(None) => return DwatError::MissingBlock(head),
+                Err(err) => return err.into(),
+            };
+            if let Err(err) = self.deliver(provider, block).await {
+                return err;
+            }
+        }
+    }
+
+    /// Delivers everything between the last delivered block and the node's
+    /// current head.
+    async fn catch_up(&mut self, provider: &Provider<Transport>) -> Result<(), DwatError> {
+        if self.last.is_none() {
+            return Ok(());
+        }
+        let head = provider.get_block_number().await?.as_u64();
+        self.fill(provider, head).await
+    }
+
+    /// Sends `block` downstream, first fetching any blocks between the last
+    /// delivered block and this one.
+    async fn deliver(
+        &mut self,
+        provider: &Provider<Transport>,
+        block: Block<H256>,
+    ) -> Result<(), DwatError> {
+        let (Some(number), Some(hash)) = (block.number, block.hash) else {
+            return Ok(());
+        };
+        if self.recent.contains(&hash) {
+            return Ok(());
+        }
+
+        self.fill(provider, number.as_u64().saturating_sub(1))
+            .await?;
+        self.send(provider, block).await
+    }
+
+    /// Fetches and sends the blocks after the last delivered one up to and
+    /// including `to`, `config.batch_size` requests at a time.
+    async fn fill(&mut self, provider: &Provider<Transport>, to: u64) -> Result<(), DwatError> {
+        let Some(last) = self.last else {
+            return Ok(());
+        };
+
+        let batch = self.config.batch_size.max(1) as u64;
+        let mut next = last + 1;
+        while next <= to {
+            let end = (next + batch - 1).min(to);
+            let blocks = try_join_all((next..=end).map(|number| async move {
+                provider
+                    .get_block(number)
+                    .await?
+                    .ok_or(DwatError::MissingBlock(number))
+            }))
+            .await?;
+
+            for block in blocks {
+                self.send(provider, block).await?;
+            }
+            next = end + 1;
+        }
+
+        Ok(())
+    }
+
+    async fn send(
+        &mut self,
+        provider: &Provider<Transport>,
+        block: Block<H256>,
+    ) -> Result<(), DwatError> {
+        let number = block.number.unwrap_or_default().as_u64();
+        let hash = block.hash.unwrap_or_default();
+
+        if let Some(quorum) = self.config.quorum.filter(|&quorum| quorum > 1) {
+            self.confirm(number, hash, quorum).await?;
+        }
+
+        let events = match &mut self.canonical {
+            Some((tracker, gate)) => {
+                let mut ready = Vec::new();
+                for event in tracker.ingest(provider, block).await? {
+                    ready.extend(gate.push(event));
+                }
+                ready.extend(gate.release(provider).await?);
+                ready
+            }
+            None => vec![ChainEvent::Applied(block)],
+        };
+        self.pending.extend(events);
+
+        self.last = Some(number);
+        self.recent.push_back(hash);
+        if self.recent.len() > RECENT {
+            self.recent.pop_front();
+        }
+        self.flush(provider).await
+    }
+
+    /// Sends the pending events, keeping those not sent yet if preparing
+    /// one fails.
+    async fn flush(&mut self, provider: &Provider<Transport>) -> Result<(), DwatError> {
+        while let Some(event) = self.pending.front() {
+            let item = T::prepare(event, provider, &self.fetcher).await?;
+            self.tx
+                .send(Ok(item))
+                .await
+                .map_err(|_| DwatError::SubscriptionClosed)?;
+            self.pending.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Waits until `quorum` endpoints report `hash` for block `number`,
+    /// asking the peers again every `QUORUM_RETRY` for up to `QUORUM_WAIT`.
+    async fn confirm(&mut self, number: u64, hash: H256, quorum: usize) -> Result<(), DwatError> {
+        let deadline = Instant::now() + QUORUM_WAIT;
+        while !self.pool.agree(self.active, number, hash, quorum).await {
+            if Instant::now() >= deadline {
+                return Err(DwatError::NoQuorum(number));
+            }
+            time::sleep(QUORUM_RETRY).await;
+        }
+        Ok(())
+    }
+
+    /// Connects to the healthiest endpoint.
+    async fn reconnect(&mut self) -> Result<Provider<Transport>, DwatError> {
+        let (active, provider) = self.pool.connect().await?;
+        self.active = active;
+        Ok(provider)
+    }
+}
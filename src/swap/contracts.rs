@@ -0,0 +1,43 @@
+//! Bindings for the pool and token contracts the swap module talks to.
+
+use ethers::contract::abigen;
+
+abigen!(
+    UniswapPool,
+    r#"[
+        function token0() external view returns (address)
+        function token1() external view returns (address)
+    ]"#
+);
+
+abigen!(
+    Erc20Approval,
+    r#"[
+        function allowance(address owner, address spender) external view returns (uint256)
+        function approve(address spender, uint256 amount) external returns (bool)
+    ]"#
+);
+
+abigen!(
+    UniswapV2Router,
+    r#"[
+        function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts)
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external returns (uint256[] amounts)
+    ]"#
+);
+
+abigen!(
+    UniswapV3Router,
+    r#"[
+        struct ExactInputSingleParams { address tokenIn; address tokenOut; uint24 fee; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; uint160 sqrtPriceLimitX96; }
+        function exactInputSingle(ExactInputSingleParams params) external payable returns (uint256 amountOut)
+    ]"#
+);
+
+abigen!(
+    UniswapV3Quoter,
+    r#"[
+        struct QuoteExactInputSingleParams { address tokenIn; address tokenOut; uint256 amountIn; uint24 fee; uint160 sqrtPriceLimitX96; }
+        function quoteExactInputSingle(QuoteExactInputSingleParams params) external returns (uint256 amountOut, uint160 sqrtPriceX96After, uint32 initializedTicksCrossed, uint256 gasEstimate)
+    ]"#
+);
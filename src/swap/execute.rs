@@ -0,0 +1,325 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ethers::{
+    contract::ContractError,
+    core::types::{
+        transaction::eip2718::TypedTransaction, Address, TransactionReceipt, H256, U256, U512,
+    },
+    middleware::SignerMiddleware,
+    providers::Middleware,
+    signers::LocalWallet,
+};
+use serde::Serialize;
+
+use crate::error::DwatError;
+use crate::swap::contracts::{
+    Erc20Approval, ExactInputSingleParams, QuoteExactInputSingleParams, UniswapV2Router,
+    UniswapV3Quoter, UniswapV3Router,
+};
+
+/// Basis points in one whole, the largest meaningful slippage tolerance.
+pub const BPS: u32 = 10_000;
+
+/// A middleware that signs with a local key.
+pub type Signed<M> = SignerMiddleware<M, LocalWallet>;
+
+/// Which router a swap goes through.
+#[derive(Debug, Clone)]
+pub enum Route {
+    /// A Uniswap V2-style router swapping along `path`, quoted from the
+    /// pools' reserves through `getAmountsOut`.
+    V2 { router: Address, path: Vec<Address> },
+    /// The Uniswap V3 `SwapRouter` swapping through a single pool, quoted by
+    /// `QuoterV2`.
+    V3 {
+        router: Address,
+        quoter: Address,
+        token_in: Address,
+        token_out: Address,
+        /// Pool fee tier in hundredths of a basis point, e.g. `3000`.
+        fee: u32,
+    },
+}
+
+impl Route {
+    fn router(&self) -> Address {
+        match self {
+            Route::V2 { router, .. } | Route::V3 { router, .. } => *router,
+        }
+    }
+
+    fn token_in(&self) -> Result<Address, DwatError> {
+        match self {
+            Route::V2 { path, .. } => path
+                .first()
+                .copied()
+                .ok_or_else(|| DwatError::Config("swap path is empty".into())),
+            Route::V3 { token_in, .. } => Ok(*token_in),
+        }
+    }
+}
+
+/// An exact-input swap to execute.
+#[derive(Debug, Clone)]
+pub struct SwapOrder {
+    pub route: Route,
+    pub amount_in: U256,
+    /// Largest acceptable shortfall against the quote, in basis points.
+    pub slippage_bps: u32,
+    /// Receiver of the output tokens; the signer when `None`.
+    pub recipient: Option<Address>,
+    /// How long the router accepts the transaction for.
+    pub deadline: Duration,
+    /// Blocks to wait for after inclusion.
+    pub confirmations: usize,
+}
+
+impl SwapOrder {
+    pub fn new(route: Route, amount_in: U256) -> Self {
+        Self {
+            route,
+            amount_in,
+            slippage_bps: 50,
+            recipient: None,
+            deadline: Duration::from_secs(300),
+            confirmations: 1,
+        }
+    }
+}
+
+/// The outcome of an executed swap.
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapExecution {
+    pub transaction_hash: H256,
+    pub quoted_amount_out: U256,
+    pub amount_out_min: U256,
+    pub receipt: TransactionReceipt,
+}
+
+/// Builds a signing middleware for `key`, bound to the provider's chain.
+pub async fn signer<M: Middleware>(provider: M, key: &str) -> Result<Signed<M>, DwatError> {
+    let wallet: LocalWallet = key
+        .parse()
+        .map_err(|_| DwatError::Config("invalid private key".into()))?;
+    SignerMiddleware::new_with_provider_chain(provider, wallet)
+        .await
+        .map_err(|err| DwatError::Contract(err.to_string()))
+}
+
+/// Approves the router if needed, quotes `order`, submits the swap with a
+/// minimum output derived from the slippage tolerance, and waits for it to
+/// be included.
+pub async fn entry_point<M: Middleware + 'static>(
+    client: Arc<Signed<M>>,
+    order: &SwapOrder,
+) -> Result<SwapExecution, DwatError> {
+    let owner = client.address();
+    let recipient = order.recipient.unwrap_or(owner);
+    check_slippage(order.slippage_bps)?;
+
+    approve(
+        client.clone(),
+        order.route.token_in()?,
+        order.route.router(),
+        order.amount_in,
+    )
+    .await?;
+
+    // Approving may have taken a few blocks, so the price and the deadline
+    // are only fixed now.
+    let quoted = quote(client.clone(), &order.route, order.amount_in).await?;
+    let amount_out_min = min_out(quoted, order.slippage_bps)?;
+    let deadline = deadline(order.deadline);
+
+    let tx = match &order.route {
+        Route::V2 { router, path } => {
+            UniswapV2Router::new(*router, client.clone())
+                .swap_exact_tokens_for_tokens(
+                    order.amount_in,
+                    amount_out_min,
+                    path.clone(),
+                    recipient,
+                    deadline,
+                )
+                .tx
+        }
+        Route::V3 {
+            router,
+            token_in,
+            token_out,
+            fee,
+            ..
+        } => {
+            UniswapV3Router::new(*router, client.clone())
+                .exact_input_single(ExactInputSingleParams {
+                    token_in: *token_in,
+                    token_out: *token_out,
+                    fee: *fee,
+                    recipient,
+                    deadline,
+                    amount_in: order.amount_in,
+                    amount_out_minimum: amount_out_min,
+                    sqrt_price_limit_x96: U256::zero(),
+                })
+                .tx
+        }
+    };
+
+    let receipt = submit(&client, tx, order.confirmations).await?;
+    Ok(SwapExecution {
+        transaction_hash: receipt.transaction_hash,
+        quoted_amount_out: quoted,
+        amount_out_min,
+        receipt,
+    })
+}
+
+/// Expected output for `amount_in` along `route` at current pool state.
+pub async fn quote<M: Middleware + 'static>(
+    client: Arc<M>,
+    route: &Route,
+    amount_in: U256,
+) -> Result<U256, DwatError> {
+    match route {
+        Route::V2 { router, path } => {
+            let amounts = UniswapV2Router::new(*router, client)
+                .get_amounts_out(amount_in, path.clone())
+                .call()
+                .await
+                .map_err(contract_err)?;
+            amounts
+                .last()
+                .copied()
+                .ok_or_else(|| DwatError::Contract("router returned no amounts".into()))
+        }
+        Route::V3 {
+            quoter,
+            token_in,
+            token_out,
+            fee,
+            ..
+        } => {
+            let (amount_out, ..) = UniswapV3Quoter::new(*quoter, client)
+                .quote_exact_input_single(QuoteExactInputSingleParams {
+                    token_in: *token_in,
+                    token_out: *token_out,
+                    amount_in,
+                    fee: *fee,
+                    sqrt_price_limit_x96: U256::zero(),
+                })
+                .call()
+                .await
+                .map_err(contract_err)?;
+            Ok(amount_out)
+        }
+    }
+}
+
+/// `quoted` reduced by `slippage_bps`, rounding down. Tolerances above
+/// [`BPS`] are rejected.
+pub fn min_out(quoted: U256, slippage_bps: u32) -> Result<U256, DwatError> {
+    check_slippage(slippage_bps)?;
+    let keep = BPS - slippage_bps;
+    U256::try_from(quoted.full_mul(U256::from(keep)) / U512::from(BPS))
+        .map_err(|_| DwatError::Config(format!("minimum output of {} overflows", quoted)))
+}
+
+fn check_slippage(slippage_bps: u32) -> Result<(), DwatError> {
+    if slippage_bps > BPS {
+        return Err(DwatError::Config(format!(
+            "slippage of {} bps exceeds {}",
+            slippage_bps, BPS
+        )));
+    }
+    Ok(())
+}
+
+/// Raises the router's allowance to `amount` if it is lower. A non-zero
+/// allowance is reset to zero first, as tokens like USDT require.
+async fn approve<M: Middleware + 'static>(
+    client: Arc<Signed<M>>,
+    token: Address,
+    spender: Address,
+    amount: U256,
+) -> Result<(), DwatError> {
+    let erc20 = Erc20Approval::new(token, client.clone());
+    let allowance = erc20
+        .allowance(client.address(), spender)
+        .call()
+        .await
+        .map_err(contract_err)?;
+    if allowance >= amount {
+        return Ok(());
+    }
+
+    if !allowance.is_zero() {
+        submit(&client, erc20.approve(spender, U256::zero()).tx, 1).await?;
+    }
+    submit(&client, erc20.approve(spender, amount).tx, 1).await?;
+    Ok(())
+}
+
+/// Signs and sends `tx`, then waits until it has `confirmations` blocks,
+/// failing if it was dropped or reverted.
+async fn submit<M: Middleware + 'static>(
+    client: &Signed<M>,
+    tx: TypedTransaction,
+    confirmations: usize,
+) -> Result<TransactionReceipt, DwatError> {
+    let pending = client
+        .send_transaction(tx, None)
+        .await
+        .map_err(|err| DwatError::Contract(err.to_string()))?;
+    let hash = *pending;
+
+    let receipt = pending
+        .confirmations(confirmations)
+        .await?
+        .ok_or_else(|| DwatError::Contract(format!("transaction {:?} was dropped", hash)))?;
+    if receipt.status != Some(1.into()) {
+        return Err(DwatError::Contract(format!(
+            "transaction {:?} reverted",
+            hash
+        )));
+    }
+    Ok(receipt)
+}
+
+fn deadline(after: Duration) -> U256 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    U256::from((now + after).as_secs())
+}
+
+fn contract_err<M: Middleware>(err: ContractError<M>) -> DwatError {
+    DwatError::Contract(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_out_applies_slippage() {
+        assert_eq!(min_out(U256::from(10_000), 50).unwrap(), U256::from(9_950));
+        assert_eq!(min_out(U256::from(999), 1).unwrap(), U256::from(998));
+        assert_eq!(min_out(U256::from(999), BPS).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn min_out_does_not_overflow_on_large_quotes() {
+        assert_eq!(min_out(U256::MAX, 0).unwrap(), U256::MAX);
+        assert_eq!(
+            min_out(U256::MAX, 50).unwrap(),
+            U256::MAX / U256::from(BPS) * U256::from(9_950)
+                + (U256::MAX % U256::from(BPS)) * U256::from(9_950) / U256::from(BPS)
+        );
+    }
+
+    #[test]
+    fn min_out_rejects_slippage_above_bps() {
+        assert!(min_out(U256::from(10_000), BPS + 1).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! DEX swap tracking and execution.
+
+pub mod contracts;
+pub mod execute;
+pub mod tracker;
+
+pub use execute::{entry_point, Route, SwapExecution, SwapOrder};
+pub use tracker::{Protocol, SwapEvent, SwapTracker};
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use ethers::{
+    abi::{self, ParamType, Token},
+    core::types::{Address, Log, H256, I256, U256},
+    providers::Middleware,
+    utils::keccak256,
+};
+use serde::Serialize;
+
+use crate::block::BlockData;
+use crate::error::DwatError;
+use crate::swap::contracts::UniswapPool;
+use crate::tokens::{optional, TokenResolver};
+
+/// Digits kept when dividing amounts into a price.
+const PRICE_DIGITS: i64 = 18;
+
+static V2_SWAP: LazyLock<H256> = LazyLock::new(|| {
+    H256(keccak256(
+        "Swap(address,uint256,uint256,uint256,uint256,address)",
+    ))
+});
+static V3_SWAP: LazyLock<H256> = LazyLock::new(|| {
+    H256(keccak256(
+        "Swap(address,address,int256,int256,uint160,uint128,int24)",
+    ))
+});
+/// PancakeSwap V3 appends the protocol fees to the Uniswap V3 event.
+static PANCAKE_V3_SWAP: LazyLock<H256> = LazyLock::new(|| {
+    H256(keccak256(
+        "Swap(address,address,int256,int256,uint160,uint128,int24,uint128,uint128)",
+    ))
+});
+
+/// The pool design a swap was made against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// Uniswap V2 and its constant-product forks.
+    UniswapV2,
+    /// Uniswap V3 and its concentrated-liquidity forks.
+    UniswapV3,
+}
+
+/// One swap through a pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapEvent {
+    pub protocol: Protocol,
+    pub pool: Address,
+    /// The account that sent the transaction, or the swap's recipient when
+    /// the transaction is not known.
+    pub trader: Address,
+    /// The contract that called the pool, usually a router.
+    pub sender: Address,
+    pub recipient: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub symbol_in: Option<String>,
+    pub symbol_out: Option<String>,
+    pub raw_amount_in: U256,
+    pub raw_amount_out: U256,
+    /// `raw_amount_in` scaled by the input token's decimals.
+    pub amount_in: BigDecimal,
+    /// `raw_amount_out` scaled by the output token's decimals.
+    pub amount_out: BigDecimal,
+    /// Units of `token_out` received per unit of `token_in`.
+    pub price: BigDecimal,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<H256>,
+    pub log_index: Option<u64>,
+}
+
+/// A pool's tokens and their decimals.
+#[derive(Debug, Clone, Copy)]
+struct Pool {
+    token0: (Address, u8),
+    token1: (Address, u8),
+}
+
+/// A swap as read from a pool's log, before tokens are resolved.
+struct RawSwap {
+    protocol: Protocol,
+    sender: Address,
+    recipient: Address,
+    /// Whether token0 went into the pool.
+    zero_for_one: bool,
+    amount_in: U256,
+    amount_out: U256,
+}
+
+/// Finds DEX swaps in logs and turns them into [`SwapEvent`]s.
+///
+/// Pool tokens are looked up on chain the first time a pool is seen and
+/// cached afterwards; token metadata comes from a [`TokenResolver`].
+pub struct SwapTracker<M> {
+    client: Arc<M>,
+    pools: HashMap<Address, Option<Pool>>,
+    tokens: TokenResolver<M>,
+}
+
+impl<M: Middleware + 'static> SwapTracker<M> {
+    pub fn new(client: Arc<M>) -> Self {
+        let tokens = TokenResolver::new(client.clone());
+        Self::with_resolver(client, tokens)
+    }
+
+    /// Uses `tokens` for token metadata, e.g. one backed by the database.
+    pub fn with_resolver(client: Arc<M>, tokens: TokenResolver<M>) -> Self {
+        Self {
+            client,
+            pools: HashMap::new(),
+            tokens,
+        }
+    }
+
+    /// All swaps in `data`, attributed to the senders of their transactions.
+    pub async fn swaps_in_block(&mut self, data: &BlockData) -> Result<Vec<SwapEvent>, DwatError> {
+        let senders: HashMap<H256, Address> = data
+            .block
+            .transactions
+            .iter()
+            .map(|tx| (tx.hash, tx.from))
+            .collect();
+
+        let mut swaps = Vec::new();
+        for log in data.logs() {
+            let trader = log
+                .transaction_hash
+                .and_then(|hash| senders.get(&hash).copied());
+            if let Some(swap) = self.decode(log, trader).await? {
+                swaps.push(swap);
+            }
+        }
+        Ok(swaps)
+    }
+
+    /// Decodes `log` if it is a swap from a pool whose tokens resolve.
+    pub async fn decode(
+        &mut self,
+        log: &Log,
+        trader: Option<Address>,
+    ) -> Result<Option<SwapEvent>, DwatError> {
+        let Some(raw) = parse(log) else {
+            return Ok(None);
+        };
+        let Some(pool) = self.pool(log.address).await? else {
+            return Ok(None);
+        };
+
+        let (token_in, token_out) = if raw.zero_for_one {
+            (pool.token0, pool.token1)
+        } else {
+            (pool.token1, pool.token0)
+        };
+        let amount_in = scale(raw.amount_in, token_in.1);
+        let amount_out = scale(raw.amount_out, token_out.1);
+        let price = if raw.amount_in.is_zero() {
+            BigDecimal::from(0)
+        } else {
+            (&amount_out / &amount_in).round(PRICE_DIGITS)
+        };
+        let symbol_in = self.tokens.resolve(token_in.0).await?.symbol;
+        let symbol_out = self.tokens.resolve(token_out.0).await?.symbol;
+
+        Ok(Some(SwapEvent {
+            protocol: raw.protocol,
+            pool: log.address,
+            trader: trader.unwrap_or(raw.recipient),
+            sender: raw.sender,
+            recipient: raw.recipient,
+            token_in: token_in.0,
+            token_out: token_out.0,
+            symbol_in,
+            symbol_out,
+            raw_amount_in: raw.amount_in,
+            raw_amount_out: raw.amount_out,
+            amount_in,
+            amount_out,
+            price,
+            block_number: log.block_number.map(|n| n.as_u64()),
+            transaction_hash: log.transaction_hash,
+            log_index: log.log_index.map(|i| i.low_u64()),
+        }))
+    }
+
+    /// Resolves a pool's tokens, remembering contracts that are not pools.
+    ///
+    /// A contract is not a pool if `token0()` or `token1()` reverts or
+    /// returns something else than an address, or if either token has no
+    /// decimals. Failures to reach the node are returned and not remembered.
+    async fn pool(&mut self, address: Address) -> Result<Option<Pool>, DwatError> {
+        if let Some(pool) = self.pools.get(&address) {
+            return Ok(*pool);
+        }
+
+        let contract = UniswapPool::new(address, self.client.clone());
+        let token0 = optional(contract.token_0().call().await, address, "token0")?;
+        let token1 = optional(contract.token_1().call().await, address, "token1")?;
+
+        let pool = match (token0, token1) {
+            (Some(token0), Some(token1)) => {
+                let decimals0 = self.tokens.resolve(token0).await?.decimals;
+                let decimals1 = self.tokens.resolve(token1).await?.decimals;
+                decimals0.zip(decimals1).map(|(decimals0, decimals1)| Pool {
+                    token0: (token0, decimals0),
+                    token1: (token1, decimals1),
+                })
+            }
+            _ => None,
+        };
+        self.pools.insert(address, pool);
+        Ok(pool)
+    }
+}
+
+fn parse(log: &Log) -> Option<RawSwap> {
+    let topic0 = *log.topics.first()?;
+    let sender = Address::from(*log.topics.get(1)?);
+    let recipient = log.topics.get(2).map(|t| Address::from(*t));
+
+    if topic0 == *V2_SWAP {
+        let mut values = abi::decode(&vec![ParamType::Uint(256); 4], &log.data)
+            .ok()?
+            .into_iter()
+            .map(Token::into_uint);
+        let (in0, in1, out0, out1) = (
+            values.next()??,
+            values.next()??,
+            values.next()??,
+            values.next()??,
+        );
+
+        let zero_for_one = !in0.is_zero() && !out1.is_zero();
+        let (amount_in, amount_out) = if zero_for_one {
+            (in0, out1)
+        } else {
+            (in1, out0)
+        };
+        return Some(RawSwap {
+            protocol: Protocol::UniswapV2,
+            sender,
+            recipient: recipient?,
+            zero_for_one,
+            amount_in,
+            amount_out,
+        });
+    }
+
+    if topic0 == *V3_SWAP || topic0 == *PANCAKE_V3_SWAP {
+        let amounts = abi::decode(
+            &[ParamType::Int(256), ParamType::Int(256)],
+            log.data.get(..64)?,
+        )
+        .ok()?;
+        let amount0 = I256::from_raw(amounts[0].clone().into_int()?);
+        let amount1 = I256::from_raw(amounts[1].clone().into_int()?);
+
+        // Positive amounts flow into the pool, negative ones out of it.
+        let zero_for_one = amount0.is_positive();
+        let (amount_in, amount_out) = if zero_for_one {
+            (amount0.unsigned_abs(), amount1.unsigned_abs())
+        } else {
+            (amount1.unsigned_abs(), amount0.unsigned_abs())
+        };
+        return Some(RawSwap {
+            protocol: Protocol::UniswapV3,
+            sender,
+            recipient: recipient?,
+            zero_for_one,
+            amount_in,
+            amount_out,
+        });
+    }
+
+    None
+}
+
+/// `amount` divided by `10^decimals`, exactly.
+fn scale(amount: U256, decimals: u8) -> BigDecimal {
+    let digits = BigInt::parse_bytes(amount.to_string().as_bytes(), 10).unwrap_or_default();
+    BigDecimal::new(digits, decimals as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::encode;
+
+    use super::*;
+
+    fn log(topic0: H256, data: Vec<Token>) -> Log {
+        Log {
+            topics: vec![
+                topic0,
+                H256::from(Address::repeat_byte(1)),
+                H256::from(Address::repeat_byte(2)),
+            ],
+            data: encode(&data).into(),
+            ..Default::default()
+        }
+    }
+
+    fn uint(value: u64) -> Token {
+        Token::Uint(U256::from(value))
+    }
+
+    fn int(value: i64) -> Token {
+        Token::Int(I256::from(value).into_raw())
+    }
+
+    /// The V3 event's data after the amounts.
+    fn v3_tail() -> Vec<Token> {
+        vec![uint(1 << 40), uint(1_000), int(-5)]
+    }
+
+    fn summary(swap: RawSwap) -> (Protocol, bool, u64, u64) {
+        (
+            swap.protocol,
+            swap.zero_for_one,
+            swap.amount_in.as_u64(),
+            swap.amount_out.as_u64(),
+        )
+    }
+
+    #[test]
+    fn parses_v2_swaps_in_both_directions() {
+        let swap = parse(&log(*V2_SWAP, vec![uint(100), uint(0), uint(0), uint(95)])).unwrap();
+        assert_eq!(swap.sender, Address::repeat_byte(1));
+        assert_eq!(swap.recipient, Address::repeat_byte(2));
+        assert_eq!(summary(swap), (Protocol::UniswapV2, true, 100, 95));
+
+        let swap = parse(&log(*V2_SWAP, vec![uint(0), uint(50), uint(48), uint(0)])).unwrap();
+        assert_eq!(summary(swap), (Protocol::UniswapV2, false, 50, 48));
+    }
+
+    #[test]
+    fn parses_v3_swaps_in_both_directions() {
+        let data = |amount0, amount1| {
+            let mut data = vec![int(amount0), int(amount1)];
+            data.extend(v3_tail());
+            data
+        };
+
+        let swap = parse(&log(*V3_SWAP, data(100, -95))).unwrap();
+        assert_eq!(summary(swap), (Protocol::UniswapV3, true, 100, 95));
+
+        let swap = parse(&log(*V3_SWAP, data(-48, 50))).unwrap();
+        assert_eq!(summary(swap), (Protocol::UniswapV3, false, 50, 48));
+    }
+
+    #[test]
+    fn parses_pancake_v3_swaps() {
+        let mut data = vec![int(-48), int(50)];
+        data.extend(v3_tail());
+        data.extend([uint(3), uint(4)]);
+
+        let swap = parse(&log(*PANCAKE_V3_SWAP, data)).unwrap();
+        assert_eq!(summary(swap), (Protocol::UniswapV3, false, 50, 48));
+    }
+
+    #[test]
+    fn skips_malformed_and_other_logs() {
+        assert!(parse(&log(*V2_SWAP, vec![uint(1), uint(2)])).is_none());
+        assert!(parse(&log(*V3_SWAP, vec![int(1)])).is_none());
+
+        let mut missing_recipient = log(*V2_SWAP, vec![uint(1), uint(0), uint(0), uint(1)]);
+        missing_recipient.topics.truncate(2);
+        assert!(parse(&missing_recipient).is_none());
+
+        let transfer = H256(keccak256("Transfer(address,address,uint256)"));
+        assert!(parse(&log(transfer, vec![uint(1)])).is_none());
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use ethers::{
+    contract::{abigen, ContractError},
+    core::types::{Address, H256, U256},
+    providers::Middleware,
+};
+use serde::Serialize;
+use tokio::task;
+
+use crate::db::{pooled, PgPool};
+use crate::error::DwatError;
+use crate::models::{hex, TokenRow};
+use crate::schema::tokens;
+use crate::transfers::{TokenStandard, TokenTransfer};
+
+abigen!(
+    Erc20Metadata,
+    r#"[
+        function name() external view returns (string)
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+        function totalSupply() external view returns (uint256)
+    ]"#
+);
+
+// Early tokens such as MKR return `bytes32` from `name()` and `symbol()`.
+abigen!(
+    Erc20Bytes32Metadata,
+    r#"[
+        function name() external view returns (bytes32)
+        function symbol() external view returns (bytes32)
+    ]"#
+);
+
+/// What a token contract reports about itself. Fields the contract does not
+/// implement are `None`; a contract that is not a token has none at all.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenMetadata {
+    pub address: Address,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    /// Supply when the metadata was read; see [`TokenResolver::refresh`].
+    pub total_supply: Option<U256>,
+}
+
+/// Reads token metadata from contracts, caching it in memory and, when a
+/// pool is given, in the `tokens` table so it survives restarts.
+///
+/// Database access blocks, so a resolver with a pool must run on a
+/// multi-threaded runtime.
+pub struct TokenResolver<M> {
+    client: Arc<M>,
+    pool: Option<PgPool>,
+    cache: HashMap<Address, TokenMetadata>,
+}
+
+impl<M: Middleware + 'static> TokenResolver<M> {
+    pub fn new(client: Arc<M>) -> Self {
+        Self {
+            client,
+            pool: None,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn with_pool(client: Arc<M>, pool: PgPool) -> Self {
+        Self {
+            pool: Some(pool),
+            ..Self::new(client)
+        }
+    }
+
+    /// Metadata of `token`, from the caches when it has been seen before.
+    pub async fn resolve(&mut self, token: Address) -> Result<TokenMetadata, DwatError> {
+        if let Some(metadata) = self.cache.get(&token) {
+            return Ok(metadata.clone());
+        }
+        if let Some(metadata) = self.load(token)? {
+            self.cache.insert(token, metadata.clone());
+            return Ok(metadata);
+        }
+        self.refresh(token).await
+    }
+
+    /// Decimals of `token`, failing if it does not report them.
+    pub async fn decimals(&mut self, token: Address) -> Result<u8, DwatError> {
+        self.resolve(token)
+            .await?
+            .decimals
+            .ok_or_else(|| DwatError::Contract(format!("decimals() of {:?} failed", token)))
+    }
+
+    /// Fills in the symbol of every transfer's token, and the decimals of
+    /// ERC-20 tokens, resolving each token once.
+    pub async fn annotate(&mut self, transfers: &mut [TokenTransfer]) -> Result<(), DwatError> {
+        for transfer in transfers {
+            let metadata = self.resolve(transfer.token).await?;
+            transfer.symbol = metadata.symbol;
+            if transfer.standard == TokenStandard::Erc20 {
+                transfer.decimals = metadata.decimals;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads `token`'s metadata from the chain again, e.g. for a current
+    /// total supply, and updates the caches.
+    pub async fn refresh(&mut self, token: Address) -> Result<TokenMetadata, DwatError> {
+        let metadata = self.fetch(token).await?;
+        self.store(&metadata)?;
+        self.cache.insert(token, metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn fetch(&self, token: Address) -> Result<TokenMetadata, DwatError> {
+        let contract = Erc20Metadata::new(token, self.client.clone());
+        let (name, symbol, decimals, total_supply) = (
+            contract.name(),
+            contract.symbol(),
+            contract.decimals(),
+            contract.total_supply(),
+        );
+        let (name, symbol, decimals, total_supply) = futures::join!(
+            name.call(),
+            symbol.call(),
+            decimals.call(),
+            total_supply.call(),
+        );
+
+        let mut name = optional(name, token, "name")?;
+        let mut symbol = optional(symbol, token, "symbol")?;
+        if name.is_none() || symbol.is_none() {
+            let legacy = Erc20Bytes32Metadata::new(token, self.client.clone());
+            if name.is_none() {
+                name = optional(legacy.name().call().await, token, "name")?.map(bytes32_string);
+            }
+            if symbol.is_none() {
+                symbol =
+                    optional(legacy.symbol().call().await, token, "symbol")?.map(bytes32_string);
+            }
+        }
+
+        Ok(TokenMetadata {
+            address: token,
+            name: name.map(clean),
+            symbol: symbol.map(clean),
+            decimals: optional(decimals, token, "decimals")?,
+            total_supply: optional(total_supply, token, "totalSupply")?,
+        })
+    }
+
+    fn load(&self, token: Address) -> Result<Option<TokenMetadata>, DwatError> {
+        let Some(pool) = &self.pool else {
+            return Ok(None);
+        };
+        task::block_in_place(|| {
+            let mut conn = pooled(pool)?;
+            let row: Option<TokenRow> = tokens::table
+                .find(hex(token))
+                .select(TokenRow::as_select())
+                .first(&mut conn)
+                .optional()?;
+            Ok(row.map(|row| TokenMetadata {
+                address: token,
+                name: row.name,
+                symbol: row.symbol,
+                decimals: row.decimals.and_then(|d| u8::try_from(d).ok()),
+                total_supply: row
+                    .total_supply
+                    .and_then(|supply| U256::from_dec_str(&supply.to_string()).ok()),
+            }))
+        })
+    }
+
+    fn store(&self, metadata: &TokenMetadata) -> Result<(), DwatError> {
+        let Some(pool) = &self.pool else {
+            return Ok(());
+        };
+        let row = TokenRow::from(metadata);
+        task::block_in_place(|| {
+            diesel::insert_into(tokens::table)
+                .values(&row)
+                .on_conflict(tokens::address)
+                .do_update()
+                .set((
+                    tokens::name.eq(excluded(tokens::name)),
+                    tokens::symbol.eq(excluded(tokens::symbol)),
+                    tokens::decimals.eq(excluded(tokens::decimals)),
+                    tokens::total_supply.eq(excluded(tokens::total_supply)),
+                    tokens::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(&mut pooled(pool)?)?;
+            Ok(())
+        })
+    }
+}
+
+/// Treats a revert or an undecodable result as the method being absent, but
+/// passes on every other failure, such as not reaching the node.
+pub(crate) fn optional<T, M: Middleware>(
+    result: Result<T, ContractError<M>>,
+    contract: Address,
+    method: &str,
+) -> Result<Option<T>, DwatError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(
+            ContractError::Revert(_)
+            | ContractError::DecodingError(_)
+            | ContractError::AbiError(_)
+            | ContractError::DetokenizationError(_),
+        ) => Ok(None),
+        Err(err) => Err(DwatError::Contract(format!(
+            "{}() of {:?}: {}",
+            method, contract, err
+        ))),
+    }
+}
+
+/// A NUL-padded `bytes32` as a string.
+fn bytes32_string(bytes: [u8; 32]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(32);
+    String::from_utf8_lossy(&H256(bytes)[..len]).into_owned()
+}
+
+/// Drops NUL characters, which Postgres rejects in text, and padding.
+fn clean(value: String) -> String {
+    value.replace('\0', "").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::{encode, Token};
+    use ethers::core::types::Bytes;
+    use ethers::providers::Provider;
+
+    use super::*;
+
+    fn transfer(standard: TokenStandard, token: Address) -> TokenTransfer {
+        TokenTransfer {
+            standard,
+            token,
+            operator: None,
+            from: Address::zero(),
+            to: Address::repeat_byte(1),
+            token_id: None,
+            value: U256::one(),
+            block_number: 0,
+            block_hash: H256::zero(),
+            transaction_hash: H256::zero(),
+            log_index: 0,
+            batch_index: 0,
+            symbol: None,
+            decimals: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn annotates_transfers_resolving_each_token_once() {
+        let (provider, mock) = Provider::mocked();
+        // `name`, `symbol`, `decimals` and `totalSupply`, served last in,
+        // first out.
+        for token in [
+            Token::Uint(U256::from(1_000)),
+            Token::Uint(U256::from(6)),
+            Token::String("USDC".into()),
+            Token::String("USD Coin".into()),
+        ] {
+            mock.push::<Bytes, Bytes>(encode(&[token]).into()).unwrap();
+        }
+        let mut resolver = TokenResolver::new(Arc::new(provider));
+
+        let token = Address::repeat_byte(0xaa);
+        let mut transfers = [
+            transfer(TokenStandard::Erc20, token),
+            transfer(TokenStandard::Erc20, token),
+            transfer(TokenStandard::Erc721, token),
+        ];
+        resolver.annotate(&mut transfers).await.unwrap();
+
+        let annotated: Vec<_> = transfers
+            .iter()
+            .map(|t| (t.symbol.as_deref(), t.decimals))
+            .collect();
+        assert_eq!(
+            annotated,
+            [
+                (Some("USDC"), Some(6)),
+                (Some("USDC"), Some(6)),
+                (Some("USDC"), None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_bytes32_name_and_symbol() {
+        let (provider, mock) = Provider::mocked();
+        let bytes32 = |value: &str| {
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.resize(32, 0);
+            Token::FixedBytes(bytes)
+        };
+        // The string `name` and `symbol` calls fail to decode a `bytes32`,
+        // so both are asked again as `bytes32`. Served last in, first out.
+        for token in [
+            bytes32("MKR"),
+            bytes32("Maker"),
+            Token::Uint(U256::from(1_000)),
+            Token::Uint(U256::from(18)),
+            bytes32("MKR"),
+            bytes32("Maker"),
+        ] {
+            mock.push::<Bytes, Bytes>(encode(&[token]).into()).unwrap();
+        }
+        let resolver = TokenResolver::new(Arc::new(provider));
+
+        let token = Address::repeat_byte(0x9f);
+        let metadata = resolver.fetch(token).await.unwrap();
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                address: token,
+                name: Some("Maker".into()),
+                symbol: Some("MKR".into()),
+                decimals: Some(18),
+                total_supply: Some(U256::from(1_000)),
+            }
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::sync::LazyLock;
+
+use ethers::{
+    abi::{self, ParamType, Token},
+    core::types::{Address, Log, H256, U256},
+    utils::keccak256,
+};
+use serde::Serialize;
+
+use crate::block::BlockData;
+
+/// `Transfer` of ERC-20 (value in the data) and ERC-721 (token ID indexed).
+static TRANSFER: LazyLock<H256> =
+    LazyLock::new(|| H256(keccak256("Transfer(address,address,uint256)")));
+static TRANSFER_SINGLE: LazyLock<H256> = LazyLock::new(|| {
+    H256(keccak256(
+        "TransferSingle(address,address,address,uint256,uint256)",
+    ))
+});
+static TRANSFER_BATCH: LazyLock<H256> = LazyLock::new(|| {
+    H256(keccak256(
+        "TransferBatch(address,address,address,uint256[],uint256[])",
+    ))
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStandard {
+    Erc20,
+    Erc721,
+    Erc1155,
+}
+
+impl TokenStandard {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenStandard::Erc20 => "erc20",
+            TokenStandard::Erc721 => "erc721",
+            TokenStandard::Erc1155 => "erc1155",
+        }
+    }
+}
+
+/// A token movement decoded from a standard transfer event. Mints come from
+/// and burns go to the zero address.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenTransfer {
+    pub standard: TokenStandard,
+    pub token: Address,
+    /// Caller of an ERC-1155 transfer.
+    pub operator: Option<Address>,
+    pub from: Address,
+    pub to: Address,
+    /// `None` for ERC-20 transfers.
+    pub token_id: Option<U256>,
+    /// Amount moved; always 1 for ERC-721.
+    pub value: U256,
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub transaction_hash: H256,
+    pub log_index: u64,
+    /// Position within an ERC-1155 `TransferBatch`, 0 otherwise.
+    pub batch_index: usize,
+    /// The token's symbol, once filled in by
+    /// [`TokenResolver::annotate`](crate::TokenResolver::annotate).
+    pub symbol: Option<String>,
+    /// Decimals of an ERC-20 token, filled in the same way.
+    pub decimals: Option<u8>,
+}
+
+/// Decodes the token transfers of every log in `data`, in log order.
+pub fn block_transfers(data: &BlockData) -> Vec<TokenTransfer> {
+    data.receipts
+        .iter()
+        .flat_map(|receipt| &receipt.logs)
+        .flat_map(transfers)
+        .collect()
+}
+
+/// Decodes `log` if it is an ERC-20, ERC-721 or ERC-1155 transfer. A
+/// `TransferBatch` yields one transfer per token ID.
+pub fn transfers(log: &Log) -> Vec<TokenTransfer> {
+    if log.removed == Some(true) {
+        return Vec::new();
+    }
+    parse(log).unwrap_or_default()
+}
+
+fn parse(log: &Log) -> Option<Vec<TokenTransfer>> {
+    let topic0 = *log.topics.first()?;
+    let address = |i: usize| log.topics.get(i).map(|topic| Address::from(*topic));
+    let transfer = |standard, operator, from, to, token_id, value, batch_index| TokenTransfer {
+        standard,
+        token: log.address,
+        operator,
+        from,
+        to,
+        token_id,
+        value,
+        block_number: log.block_number.unwrap_or_default().as_u64(),
+        block_hash: log.block_hash.unwrap_or_default(),
+        transaction_hash: log.transaction_hash.unwrap_or_default(),
+        log_index: log.log_index.unwrap_or_default().as_u64(),
+        batch_index,
+        symbol: None,
+        decimals: None,
+    };
+
+    if topic0 == *TRANSFER {
+        let (from, to) = (address(1)?, address(2)?);
+        // ERC-721 indexes the token ID; ERC-20 puts the value in the data.
+        return match log.topics.len() {
+            3 => {
+                let value = U256::from_big_endian(log.data.get(..32)?);
+                Some(vec![transfer(
+                    TokenStandard::Erc20,
+                    None,
+                    from,
+                    to,
+                    None,
+                    value,
+                    0,
+                )])
+            }
+            4 => {
+                let token_id = U256::from_big_endian(log.topics[3].as_bytes());
+                Some(vec![transfer(
+                    TokenStandard::Erc721,
+                    None,
+                    from,
+                    to,
+                    Some(token_id),
+                    U256::one(),
+                    0,
+                )])
+            }
+            _ => None,
+        };
+    }
+
+    if topic0 == *TRANSFER_SINGLE {
+        let (operator, from, to) = (address(1)?, address(2)?, address(3)?);
+        let mut values = abi::decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data)
+            .ok()?
+            .into_iter()
+            .map(Token::into_uint);
+        let (id, value) = (values.next()??, values.next()??);
+        return Some(vec![transfer(
+            TokenStandard::Erc1155,
+            Some(operator),
+            from,
+            to,
+            Some(id),
+            value,
+            0,
+        )]);
+    }
+
+    if topic0 == *TRANSFER_BATCH {
+        let (operator, from, to) = (address(1)?, address(2)?, address(3)?);
+        let array = ParamType::Array(Box::new(ParamType::Uint(256)));
+        let mut arrays = abi::decode(&[array.clone(), array], &log.data)
+            .ok()?
+            .into_iter()
+            .map(Token::into_array);
+        let (ids, values) = (arrays.next()??, arrays.next()??);
+        if ids.len() != values.len() {
+            return None;
+        }
+        return ids
+            .into_iter()
+            .zip(values)
+            .enumerate()
+            .map(|(i, (id, value))| {
+                Some(transfer(
+                    TokenStandard::Erc1155,
+                    Some(operator),
+                    from,
+                    to,
+                    Some(id.into_uint()?),
+                    value.into_uint()?,
+                    i,
+                ))
+            })
+            .collect();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::encode;
+
+    use super::*;
+
+    fn log(topic0: H256, indexed: &[Address], data: Vec<u8>) -> Log {
+        Log {
+            address: Address::repeat_byte(0xee),
+            topics: std::iter::once(topic0)
+                .chain(indexed.iter().map(|&address| H256::from(address)))
+                .collect(),
+            data: data.into(),
+            log_index: Some(U256::from(3)),
+            ..Default::default()
+        }
+    }
+
+    fn addresses() -> (Address, Address, Address) {
+        (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        )
+    }
+
+    #[test]
+    fn parses_erc20_transfers() {
+        let (from, to, _) = addresses();
+        let data = encode(&[Token::Uint(U256::from(250))]);
+        let transfers = transfers(&log(*TRANSFER, &[from, to], data));
+
+        assert_eq!(transfers.len(), 1);
+        let transfer = &transfers[0];
+        assert_eq!(transfer.standard, TokenStandard::Erc20);
+        assert_eq!(transfer.token, Address::repeat_byte(0xee));
+        assert_eq!((transfer.from, transfer.to), (from, to));
+        assert_eq!(transfer.token_id, None);
+        assert_eq!(transfer.value, U256::from(250));
+        assert_eq!(transfer.log_index, 3);
+    }
+
+    #[test]
+    fn parses_erc721_transfers() {
+        let (from, to, _) = addresses();
+        let mut log = log(*TRANSFER, &[from, to], Vec::new());
+        log.topics.push(H256::from_low_u64_be(77));
+
+        let transfers = transfers(&log);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].standard, TokenStandard::Erc721);
+        assert_eq!(transfers[0].token_id, Some(U256::from(77)));
+        assert_eq!(transfers[0].value, U256::one());
+    }
+
+    #[test]
+    fn parses_erc1155_single_transfers() {
+        let (operator, from, to) = addresses();
+        let data = encode(&[Token::Uint(U256::from(5)), Token::Uint(U256::from(10))]);
+        let transfers = transfers(&log(*TRANSFER_SINGLE, &[operator, from, to], data));
+
+        assert_eq!(transfers.len(), 1);
+        let transfer = &transfers[0];
+        assert_eq!(transfer.standard, TokenStandard::Erc1155);
+        assert_eq!(transfer.operator, Some(operator));
+        assert_eq!((transfer.from, transfer.to), (from, to));
+        assert_eq!(transfer.token_id, Some(U256::from(5)));
+        assert_eq!(transfer.value, U256::from(10));
+    }
+
+    #[test]
+    fn parses_erc1155_batch_transfers() {
+        let (operator, from, to) = addresses();
+        let uints = |values: &[u64]| {
+            Token::Array(values.iter().map(|&v| Token::Uint(U256::from(v))).collect())
+        };
+        let data = encode(&[uints(&[1, 2]), uints(&[100, 200])]);
+        let batch = transfers(&log(*TRANSFER_BATCH, &[operator, from, to], data));
+
+        let parsed: Vec<_> = batch
+            .iter()
+            .map(|t| {
+                (
+                    t.token_id.unwrap().as_u64(),
+                    t.value.as_u64(),
+                    t.batch_index,
+                )
+            })
+            .collect();
+        assert_eq!(parsed, [(1, 100, 0), (2, 200, 1)]);
+
+        let data = encode(&[uints(&[1, 2]), uints(&[100])]);
+        // Mismatched ID and value counts.
+        assert!(transfers(&log(*TRANSFER_BATCH, &[operator, from, to], data)).is_empty());
+    }
+
+    #[test]
+    fn skips_malformed_removed_and_other_logs() {
+        let (from, to, _) = addresses();
+        // ERC-20 transfer without its value.
+        assert!(transfers(&log(*TRANSFER, &[from, to], Vec::new())).is_empty());
+        // Transfer with too few indexed topics.
+        let data = encode(&[Token::Uint(U256::one())]);
+        assert!(transfers(&log(*TRANSFER, &[from], data.clone())).is_empty());
+
+        let mut removed = log(*TRANSFER, &[from, to], data.clone());
+        removed.removed = Some(true);
+        assert!(transfers(&removed).is_empty());
+
+        let approval = H256(keccak256("Approval(address,address,uint256)"));
+        assert!(transfers(&log(approval, &[from, to], data)).is_empty());
+    }
+}
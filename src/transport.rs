@@ -0,0 +1,96 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use ethers::{
+    core::types::U256,
+    providers::{Http, Ipc, JsonRpcClient, Provider, ProviderError, PubsubClient, Ws},
+};
+use futures::stream::{BoxStream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::RawValue;
+
+use crate::error::DwatError;
+
+/// A JSON-RPC connection to a node over WebSocket, IPC or HTTP.
+///
+/// The transport is picked from the endpoint: `ws://` and `wss://` URLs use
+/// a WebSocket, `http://` and `https://` URLs use HTTP, and anything else is
+/// taken as the path of an IPC socket (an `ipc://` prefix is optional).
+/// HTTP has no subscriptions, so streams poll it instead.
+#[derive(Debug, Clone)]
+pub enum Transport {
+    Ws(Ws),
+    Ipc(Ipc),
+    Http(Http),
+}
+
+impl Transport {
+    pub async fn connect(endpoint: &str) -> Result<Self, DwatError> {
+        if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+            let ws = Ws::connect(endpoint).await.map_err(ProviderError::from)?;
+            Ok(Transport::Ws(ws))
+        } else if polls(endpoint) {
+            let http = Http::from_str(endpoint).map_err(|err| {
+                DwatError::Config(format!("invalid endpoint {}: {}", endpoint, err))
+            })?;
+            Ok(Transport::Http(http))
+        } else {
+            let path = endpoint.strip_prefix("ipc://").unwrap_or(endpoint);
+            let ipc = Ipc::connect(path).await.map_err(ProviderError::from)?;
+            Ok(Transport::Ipc(ipc))
+        }
+    }
+
+    /// Connects to `endpoint` and wraps the transport in a provider.
+    pub async fn provider(endpoint: &str) -> Result<Provider<Self>, DwatError> {
+        Ok(Provider::new(Self::connect(endpoint).await?))
+    }
+
+    /// Whether the node can push notifications over this transport.
+    pub fn is_pubsub(&self) -> bool {
+        !matches!(self, Transport::Http(_))
+    }
+}
+
+/// Whether `endpoint` is served over HTTP and has to be polled.
+pub fn polls(endpoint: &str) -> bool {
+    endpoint.starts_with("http://") || endpoint.starts_with("https://")
+}
+
+#[async_trait]
+impl JsonRpcClient for Transport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, ProviderError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Transport::Ws(ws) => ws.request(method, params).await.map_err(Into::into),
+            Transport::Ipc(ipc) => ipc.request(method, params).await.map_err(Into::into),
+            Transport::Http(http) => http.request(method, params).await.map_err(Into::into),
+        }
+    }
+}
+
+impl PubsubClient for Transport {
+    type NotificationStream = BoxStream<'static, Box<RawValue>>;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, ProviderError> {
+        match self {
+            Transport::Ws(ws) => Ok(ws.subscribe(id).map_err(ProviderError::from)?.boxed()),
+            Transport::Ipc(ipc) => Ok(ipc.subscribe(id).map_err(ProviderError::from)?.boxed()),
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), ProviderError> {
+        match self {
+            Transport::Ws(ws) => ws.unsubscribe(id).map_err(Into::into),
+            Transport::Ipc(ipc) => ipc.unsubscribe(id).map_err(Into::into),
+            Transport::Http(_) => Err(ProviderError::UnsupportedRPC),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Writes to the Postgres database named by `DATABASE_URL`; skipped when it
+//! is not set. The block stored here uses a made-up hash and number far past
+//! any real chain, and is reverted again at the end.
+
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use dwat::models::hex;
+use dwat::schema::{blocks, logs, token_balances, token_transfers, transactions};
+use dwat::{BlockData, ChainBlock, PgSink, Sink};
+use ethers::core::types::{
+    Address, Block, Bytes, Log, Transaction, TransactionReceipt, H256, U256, U64,
+};
+use ethers::utils::keccak256;
+
+const NUMBER: u64 = 900_000_001;
+
+fn block() -> BlockData {
+    let hash = H256::repeat_byte(0xd1);
+    let tx_hash = H256::repeat_byte(0xd2);
+    let token = Address::repeat_byte(0xd3);
+    let (from, to) = (Address::repeat_byte(0xd4), Address::repeat_byte(0xd5));
+
+    let transaction = Transaction {
+        hash: tx_hash,
+        block_hash: Some(hash),
+        block_number: Some(U64::from(NUMBER)),
+        transaction_index: Some(U64::zero()),
+        from,
+        to: Some(token),
+        ..Default::default()
+    };
+    let log = Log {
+        address: token,
+        topics: vec![
+            H256::from(keccak256("Transfer(address,address,uint256)")),
+            H256::from(from),
+            H256::from(to),
+        ],
+        data: Bytes::from(H256::from_low_u64_be(250).as_bytes().to_vec()),
+        block_hash: Some(hash),
+        block_number: Some(U64::from(NUMBER)),
+        transaction_hash: Some(tx_hash),
+        transaction_index: Some(U64::zero()),
+        log_index: Some(U256::zero()),
+        ..Default::default()
+    };
+    let receipt = TransactionReceipt {
+        transaction_hash: tx_hash,
+        block_hash: Some(hash),
+        block_number: Some(U64::from(NUMBER)),
+        from,
+        to: Some(token),
+        logs: vec![log],
+        status: Some(U64::one()),
+        ..Default::default()
+    };
+
+    BlockData {
+        block: Block {
+            hash: Some(hash),
+            number: Some(U64::from(NUMBER)),
+            parent_hash: H256::repeat_byte(0xd0),
+            timestamp: U256::from(1_700_000_000),
+            transactions: vec![transaction],
+            ..Default::default()
+        },
+        receipts: vec![receipt],
+    }
+}
+
+fn balance(sink: &mut PgSink, holder: Address) -> Option<BigDecimal> {
+    token_balances::table
+        .filter(token_balances::holder.eq(hex(holder)))
+        .filter(token_balances::token.eq(hex(Address::repeat_byte(0xd3))))
+        .select(token_balances::balance)
+        .first(sink.conn())
+        .optional()
+        .unwrap()
+}
+
+#[test]
+fn applies_a_block_twice_and_reverts_it() {
+    if std::env::var("DATABASE_URL").is_err() {
+        eprintln!("DATABASE_URL not set, skipping");
+        return;
+    }
+    let mut sink = PgSink::from_env().unwrap();
+    let block = ChainBlock::from(block());
+    let hash = block.head.hash.clone();
+    let (from, to) = (Address::repeat_byte(0xd4), Address::repeat_byte(0xd5));
+
+    // Leftovers from an interrupted run.
+    sink.revert(&block.head).unwrap();
+
+    sink.apply(&block).unwrap();
+    sink.apply(&block).unwrap();
+
+    let count = |sink: &mut PgSink| {
+        let conn = sink.conn();
+        let blocks: i64 = blocks::table
+            .filter(blocks::hash.eq(&hash))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        let txs: i64 = transactions::table
+            .filter(transactions::block_hash.eq(&hash))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        let logs: i64 = logs::table
+            .filter(logs::block_hash.eq(&hash))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        let transfers: i64 = token_transfers::table
+            .filter(token_transfers::block_hash.eq(&hash))
+            .count()
+            .get_result(conn)
+            .unwrap();
+        (blocks, txs, logs, transfers)
+    };
+    assert_eq!(count(&mut sink), (1, 1, 1, 1));
+    assert_eq!(balance(&mut sink, to), Some(BigDecimal::from(250)));
+    assert_eq!(balance(&mut sink, from), Some(BigDecimal::from(-250)));
+
+    let stored = sink.stored(NUMBER).unwrap().unwrap();
+    assert_eq!(stored.hash, block.head.hash);
+    assert_eq!(stored.parent_hash, block.head.parent_hash);
+
+    sink.revert(&stored).unwrap();
+    assert_eq!(count(&mut sink), (0, 0, 0, 0));
+    assert_eq!(balance(&mut sink, to), None);
+    assert_eq!(balance(&mut sink, from), None);
+    assert!(sink.stored(NUMBER).unwrap().is_none());
+}
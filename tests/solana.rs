@@ -0,0 +1,47 @@
+//! Streams blocks from a local `solana-test-validator`. Set
+//! `SOLANA_TEST_VALIDATOR` to its RPC endpoint (usually
+//! `http://127.0.0.1:8899`) to run them; they are skipped otherwise.
+
+use std::env;
+use std::time::Duration;
+
+use dwat::{SolanaBlock, SolanaBlockStream, SolanaConfig};
+use futures::StreamExt;
+use tokio::time::timeout;
+
+/// Generous for a validator producing a slot every 400ms.
+const WAIT: Duration = Duration::from_secs(30);
+
+async fn blocks(config: SolanaConfig, count: usize) -> Vec<SolanaBlock> {
+    let stream = SolanaBlockStream::connect_with(config).await.unwrap();
+    timeout(WAIT, stream.take(count).map(Result::unwrap).collect())
+        .await
+        .expect("validator produced no blocks")
+}
+
+#[tokio::test]
+async fn streams_linked_blocks_and_resumes_after_a_slot() {
+    let Ok(url) = env::var("SOLANA_TEST_VALIDATOR") else {
+        eprintln!("SOLANA_TEST_VALIDATOR not set, skipping");
+        return;
+    };
+
+    let first = blocks(SolanaConfig::new(&url), 3).await;
+    for pair in first.windows(2) {
+        assert_eq!(pair[1].block.parent_slot, pair[0].slot);
+    }
+
+    // Resuming from a block already seen backfills from the next one.
+    let resume_from = first[0].slot;
+    let resumed = blocks(
+        SolanaConfig {
+            resume_from: Some(resume_from),
+            ..SolanaConfig::new(&url)
+        },
+        2,
+    )
+    .await;
+    assert_eq!(resumed[0].block.parent_slot, resume_from);
+    assert_eq!(resumed[0].slot, first[1].slot);
+    assert_eq!(resumed[1].block.parent_slot, resumed[0].slot);
+}
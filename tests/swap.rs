@@ -0,0 +1,92 @@
+//! Swaps against an anvil node forking Ethereum mainnet, started with e.g.
+//! `anvil --fork-url <mainnet rpc>`. Set `ANVIL_URL` to its endpoint to run
+//! them; they are skipped otherwise.
+
+use std::env;
+use std::sync::Arc;
+
+use dwat::swap::contracts::Erc20Approval;
+use dwat::swap::execute::signer;
+use dwat::swap::{entry_point, Route, SwapOrder};
+use ethers::{
+    contract::abigen,
+    core::types::{Address, TransactionRequest, U256},
+    providers::{Http, Middleware, Provider},
+    utils::parse_ether,
+};
+
+/// The first of anvil's default funded accounts.
+const ANVIL_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+/// Reverts `approve` from one non-zero allowance to another.
+const USDT: &str = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
+const UNISWAP_V2_ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+
+abigen!(
+    Erc20Balance,
+    r#"[
+        function balanceOf(address owner) external view returns (uint256)
+    ]"#
+);
+
+fn address(s: &str) -> Address {
+    s.parse().unwrap()
+}
+
+#[tokio::test]
+async fn approves_and_swaps_through_v2_router() {
+    let Ok(url) = env::var("ANVIL_URL") else {
+        eprintln!("ANVIL_URL not set, skipping");
+        return;
+    };
+    let provider = Provider::<Http>::try_from(url).unwrap();
+    let client = Arc::new(signer(provider, ANVIL_KEY).await.unwrap());
+    let (weth, usdt, router) = (address(WETH), address(USDT), address(UNISWAP_V2_ROUTER));
+
+    // Sending ether to WETH wraps it.
+    let wrap = TransactionRequest::new()
+        .to(weth)
+        .value(parse_ether(1).unwrap());
+    client
+        .send_transaction(wrap, None)
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    let order = SwapOrder::new(
+        Route::V2 {
+            router,
+            path: vec![weth, usdt],
+        },
+        parse_ether(1).unwrap(),
+    );
+    let execution = entry_point(client.clone(), &order).await.unwrap();
+    let balance = Erc20Balance::new(usdt, client.clone())
+        .balance_of(client.address())
+        .call()
+        .await
+        .unwrap();
+    assert!(balance >= execution.amount_out_min);
+    assert!(!balance.is_zero());
+
+    // Leave a small non-zero allowance that USDT will not raise directly.
+    Erc20Approval::new(usdt, client.clone())
+        .approve(router, U256::one())
+        .send()
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    let order = SwapOrder::new(
+        Route::V2 {
+            router,
+            path: vec![usdt, weth],
+        },
+        balance,
+    );
+    let execution = entry_point(client.clone(), &order).await.unwrap();
+    assert_eq!(execution.receipt.status, Some(1.into()));
+}